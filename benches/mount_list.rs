@@ -0,0 +1,48 @@
+use bevy_dioxus::{dioxus::prelude::*, prelude::*, testing::DioxusTestApp, DioxusUiRoot};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+const ROWS: usize = 10_000;
+
+criterion_group!(benches, mount_list);
+criterion_main!(benches);
+
+fn mount_list(c: &mut Criterion) {
+    c.bench_function("mount 10k rows", |b| {
+        b.iter_batched(
+            || DioxusTestApp::with_root(DioxusUiRoot::with_props(List, ListProps { rows: 0 })),
+            |mut app| {
+                let root_entity = app.root_entity();
+                app.world_mut()
+                    .entity_mut(root_entity)
+                    .insert(DioxusUiRoot::with_props(List, ListProps { rows: ROWS }));
+                app.update();
+                app
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+#[derive(Props, PartialEq, Clone)]
+struct ListProps {
+    rows: usize,
+}
+
+#[component]
+fn List(cx: Scope<ListProps>) -> Element {
+    render! {
+        node {
+            flex_direction: "column",
+            for row in 0..cx.props.rows {
+                node {
+                    key: "{row}",
+                    padding: "4",
+                    justify_content: "space-between",
+                    background_color: "#1e293b",
+                    "Row {row}"
+                    node { width: "16", height: "16", background_color: "#4f46e5" }
+                }
+            }
+        }
+    }
+}
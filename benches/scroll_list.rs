@@ -0,0 +1,70 @@
+//! Scrolls a window over a keyed 5k-item list, so every frame removes the rows scrolled out of
+//! view and loads templates for the rows scrolled in. Runs once with template reuse and once with
+//! DioxusUiTemplatePoolSize(0), which despawns removed rows and spawns new ones instead.
+
+use bevy_dioxus::{
+    dioxus::prelude::*, prelude::*, testing::DioxusTestApp, DioxusUiRoot, DioxusUiTemplatePoolSize,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const ITEMS: usize = 5_000;
+const VISIBLE_ITEMS: usize = 100;
+const ITEMS_PER_FRAME: usize = 10;
+
+criterion_group!(benches, scroll_list);
+criterion_main!(benches);
+
+fn scroll_list(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scroll 5k-item list");
+    for (name, pool_size) in [("pooled", None), ("despawn and respawn", Some(0))] {
+        let mut app = DioxusTestApp::with_root(DioxusUiRoot::with_props(
+            ScrollList,
+            ScrollListProps { offset: 0 },
+        ));
+        if let Some(pool_size) = pool_size {
+            app.world_mut()
+                .insert_resource(DioxusUiTemplatePoolSize(pool_size));
+        }
+        let root_entity = app.root_entity();
+        let mut offset = 0;
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                offset = (offset + ITEMS_PER_FRAME) % (ITEMS - VISIBLE_ITEMS);
+                app.world_mut()
+                    .entity_mut(root_entity)
+                    .insert(DioxusUiRoot::with_props(
+                        ScrollList,
+                        ScrollListProps { offset },
+                    ));
+                app.update();
+            })
+        });
+    }
+    group.finish();
+}
+
+#[derive(Props, PartialEq, Clone)]
+struct ScrollListProps {
+    offset: usize,
+}
+
+#[component]
+fn ScrollList(cx: Scope<ScrollListProps>) -> Element {
+    let offset = cx.props.offset;
+    render! {
+        node {
+            flex_direction: "column",
+            for item in offset..offset + VISIBLE_ITEMS {
+                node {
+                    key: "{item}",
+                    padding: "4",
+                    justify_content: "space-between",
+                    background_color: "#1e293b",
+                    "Item {item} of {ITEMS}"
+                    node { width: "16", height: "16", background_color: "#4f46e5" }
+                }
+            }
+        }
+    }
+}
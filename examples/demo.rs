@@ -7,7 +7,7 @@ use bevy::{
     DefaultPlugins, render::color::Color,
 };
 use bevy_dioxus::{
-    bevy_mod_picking::DefaultPickingPlugins, colors::*, dioxus::prelude::*, hooks::*,
+    bevy_mod_picking::DefaultPickingPlugins, dioxus::prelude::*, hooks::*,
     DioxusUiBundle, DioxusUiPlugin, DioxusUiRoot,
     prelude::*
 };
@@ -15,10 +15,10 @@ use bevy_dioxus::{
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, DioxusUiPlugin, DefaultPickingPlugins))
+        .add_plugins((DefaultPlugins, DioxusUiPlugin::default(), DefaultPickingPlugins))
         .add_systems(Startup, |mut commands: Commands| {
             commands.spawn(DioxusUiBundle {
-                dioxus_ui_root: DioxusUiRoot(Editor),
+                dioxus_ui_root: DioxusUiRoot::new(Editor),
                 node_bundle: NodeBundle::default(),
             });
             commands.spawn((Camera2dBundle::default(), Name::new("Camera")));
@@ -72,7 +72,7 @@ fn SceneTree<'a>(cx: Scope, selected_entity: &'a UseState<Option<Entity>>) -> El
                                 selected_entity.set(Some(entity));
                             },
                             padding: "8",
-                            background_color: if Some(entity) == ***selected_entity { INDIGO_600 } else { NEUTRAL_800 },
+                            background_color: if Some(entity) == ***selected_entity { "var(--indigo-600)" } else { "var(--neutral-800)" },
                             match name.name {
                                 Some(name) => format!("{name}"),
                                 _ => format!("Entity ({:?})", name.entity)
@@ -122,7 +122,7 @@ fn EntityInspector<'a>(cx: Scope, selected_entity: &'a UseState<Option<Entity>>)
                     for (name, component_id, type_id, size) in components {
                         node {
                             padding: "8",
-                            background_color: NEUTRAL_800,
+                            background_color: "var(--neutral-800)",
                             
                             node {
                                 "Component: {name}"
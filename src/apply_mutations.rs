@@ -1,9 +1,24 @@
-use crate::events::is_supported_event;
+use crate::{
+    color::parse_color,
+    events::is_supported_event,
+    stylesheet::{DioxusStylesheet, StyleAttributes},
+    text_style::InheritedTextStyle,
+    theme::Theme,
+    DioxusUiTemplatePoolSize, DioxusUiTestId,
+};
 use bevy::{
-    ecs::{entity::Entity, system::Command, world::World},
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        entity::Entity,
+        system::Command,
+        world::{EntityWorldMut, Mut, World},
+    },
     hierarchy::{BuildWorldChildren, Children, DespawnRecursive, Parent},
+    log::error,
+    math::Vec2,
     prelude::default,
-    render::color::Color,
+    render::{color::Color, view::Visibility},
     text::{Text, TextLayoutInfo, TextStyle},
     ui::{
         node_bundles::{NodeBundle, TextBundle},
@@ -16,19 +31,35 @@ use dioxus::core::{
     BorrowedAttributeValue, ElementId, Mutation, Mutations, Template, TemplateAttribute,
     TemplateNode,
 };
+use std::mem;
 
-pub fn apply_mutations(
-    mutations: Mutations,
-    element_id_to_bevy_ui_entity: &mut HashMap<ElementId, Entity>,
-    bevy_ui_entity_to_element_id: &mut EntityHashMap<Entity, ElementId>,
-    templates: &mut HashMap<String, BevyTemplate>,
-    root_entity: Entity,
-    world: &mut World,
-) {
+/// The parts of a UiRoot that mutations are applied to, borrowed separately from its VirtualDom,
+/// which the mutations borrow from.
+pub struct MutationTarget<'a> {
+    pub element_id_to_bevy_ui_entity: &'a mut HashMap<ElementId, Entity>,
+    pub bevy_ui_entity_to_element_id: &'a mut EntityHashMap<Entity, ElementId>,
+    pub templates: &'a mut HashMap<String, BevyTemplate>,
+    pub template_instances: &'a mut EntityHashMap<Entity, TemplateInstance>,
+    pub root_entity: Entity,
+    pub viewport_size: Vec2,
+}
+
+pub fn apply_mutations(mutations: Mutations, target: MutationTarget, world: &mut World) {
+    let MutationTarget {
+        element_id_to_bevy_ui_entity,
+        bevy_ui_entity_to_element_id,
+        templates,
+        template_instances,
+        root_entity,
+        viewport_size,
+    } = target;
+    let mut removed_nodes = false;
+
+    let (stylesheet, theme) = (world.resource(), world.resource());
     for new_template in mutations.templates {
         templates.insert(
             new_template.name.to_owned(),
-            BevyTemplate::from_dioxus(&new_template),
+            BevyTemplate::from_dioxus(&new_template, stylesheet, theme, viewport_size),
         );
     }
 
@@ -59,8 +90,12 @@ pub fn apply_mutations(
                 stack.push(entity);
             }
             Mutation::CreateTextNode { value, id } => {
-                let entity =
-                    BevyTemplateNode::from_dioxus(&TemplateNode::Text { text: value }).spawn(world);
+                let entity = world
+                    .spawn(TextBundle {
+                        text: text_section(value),
+                        ..default()
+                    })
+                    .id();
                 element_id_to_bevy_ui_entity.insert(id, entity);
                 bevy_ui_entity_to_element_id.insert(entity, id);
                 stack.push(entity);
@@ -80,7 +115,9 @@ pub fn apply_mutations(
                 bevy_ui_entity_to_element_id.insert(entity, id);
             }
             Mutation::LoadTemplate { name, index, id } => {
-                let entity = templates[name].roots[index].spawn(world);
+                let (entity, template_instance) =
+                    templates.get_mut(name).unwrap().instantiate(index, world);
+                template_instances.insert(entity, template_instance);
                 element_id_to_bevy_ui_entity.insert(id, entity);
                 bevy_ui_entity_to_element_id.insert(entity, id);
                 stack.push(entity);
@@ -100,12 +137,15 @@ pub fn apply_mutations(
                     .unwrap();
                 parent.insert_children(index, &new_nodes);
 
-                DespawnRecursive { entity: existing }.apply(world);
-                // TODO: We're not removing child entities from the element maps
-                if let Some(existing_element_id) = bevy_ui_entity_to_element_id.remove(&existing) {
-                    element_id_to_bevy_ui_entity.remove(&existing_element_id);
-                } 
-            },
+                forget_node(
+                    existing,
+                    element_id_to_bevy_ui_entity,
+                    bevy_ui_entity_to_element_id,
+                    world,
+                );
+                remove_node(existing, templates, template_instances, world);
+                removed_nodes = true;
+            }
             Mutation::ReplacePlaceholder { path, m } => {
                 let mut existing = stack[stack.len() - m - 1];
                 for index in path {
@@ -123,11 +163,14 @@ pub fn apply_mutations(
                 let new = stack.drain((stack.len() - m)..).collect::<Vec<Entity>>();
                 existing_parent.insert_children(existing_index, &new);
 
-                DespawnRecursive { entity: existing }.apply(world);
-                // TODO: We're not removing child entities from the element maps
-                if let Some(existing_element_id) = bevy_ui_entity_to_element_id.remove(&existing) {
-                    element_id_to_bevy_ui_entity.remove(&existing_element_id);
-                } 
+                forget_node(
+                    existing,
+                    element_id_to_bevy_ui_entity,
+                    bevy_ui_entity_to_element_id,
+                    world,
+                );
+                remove_node(existing, templates, template_instances, world);
+                removed_nodes = true;
             }
             Mutation::InsertAfter { id, m } => {
                 let entity = element_id_to_bevy_ui_entity[&id];
@@ -170,11 +213,49 @@ pub fn apply_mutations(
                     }
                 };
 
-                let (mut style, mut background_color) = world
-                    .query::<(&mut Style, &mut BackgroundColor)>()
-                    .get_mut(world, element_id_to_bevy_ui_entity[&id])
-                    .unwrap();
-                set_style_attribute(name, value, &mut style, &mut background_color);
+                let entity = element_id_to_bevy_ui_entity[&id];
+                if name == TEST_ID_ATTRIBUTE {
+                    world
+                        .entity_mut(entity)
+                        .insert(DioxusUiTestId(value.to_owned()));
+                    continue;
+                }
+
+                world.resource_scope(|world, stylesheet: Mut<DioxusStylesheet>| {
+                    world.resource_scope(|world, theme: Mut<Theme>| {
+                        let mut entity = world.entity_mut(entity);
+                        let mut node_style = NodeStyle::get(&entity);
+                        match entity.get_mut::<StyleAttributes>() {
+                            Some(mut style_attributes)
+                                if name == CLASS_ATTRIBUTE
+                                    || !style_attributes.class.is_empty() =>
+                            {
+                                style_attributes.set(name, value);
+                                node_style =
+                                    style_attributes.resolve(&stylesheet, &theme, viewport_size);
+                            }
+                            // Without classes, nothing can override the new attribute
+                            Some(mut style_attributes) => {
+                                style_attributes.set(name, value);
+                                set_style_attribute(
+                                    name,
+                                    value,
+                                    &theme,
+                                    viewport_size,
+                                    &mut node_style,
+                                );
+                            }
+                            None => set_style_attribute(
+                                name,
+                                value,
+                                &theme,
+                                viewport_size,
+                                &mut node_style,
+                            ),
+                        }
+                        node_style.apply(&mut entity);
+                    });
+                });
             }
             Mutation::SetText { value, id } => {
                 world
@@ -189,46 +270,321 @@ pub fn apply_mutations(
             Mutation::RemoveEventListener { .. } => {}
             Mutation::Remove { id } => {
                 let existing = element_id_to_bevy_ui_entity[&id];
-                DespawnRecursive {
-                    entity: existing,
-                }
-                .apply(world);
-                // TODO: We're not removing child entities from the element maps
-                if let Some(existing_element_id) = bevy_ui_entity_to_element_id.remove(&existing) {
-                    element_id_to_bevy_ui_entity.remove(&existing_element_id);
-                }
+                forget_node(
+                    existing,
+                    element_id_to_bevy_ui_entity,
+                    bevy_ui_entity_to_element_id,
+                    world,
+                );
+                remove_node(existing, templates, template_instances, world);
+                removed_nodes = true;
             }
             Mutation::PushRoot { id } => stack.push(element_id_to_bevy_ui_entity[&id]),
         }
     }
+
+    // Template instances nested in despawned nodes are gone as well
+    if removed_nodes {
+        template_instances.retain(|entity, _| world.get_entity(*entity).is_some());
+    }
+}
+
+/// Removes a node and its descendants from the element maps, as dioxus frees all of their
+/// ElementIds along with the node. Must run before the node is parked or despawned.
+fn forget_node(
+    entity: Entity,
+    element_id_to_bevy_ui_entity: &mut HashMap<ElementId, Entity>,
+    bevy_ui_entity_to_element_id: &mut EntityHashMap<Entity, ElementId>,
+    world: &World,
+) {
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        if let Some(element_id) = bevy_ui_entity_to_element_id.remove(&entity) {
+            if element_id_to_bevy_ui_entity.get(&element_id) == Some(&entity) {
+                element_id_to_bevy_ui_entity.remove(&element_id);
+            }
+        }
+        if let Some(children) = world.get::<Children>(entity) {
+            stack.extend(children.iter());
+        }
+    }
+}
+
+/// Parks template instances so the next LoadTemplate of the same template can reuse them, and
+/// despawns anything else.
+fn remove_node(
+    entity: Entity,
+    templates: &mut HashMap<String, BevyTemplate>,
+    template_instances: &mut EntityHashMap<Entity, TemplateInstance>,
+    world: &mut World,
+) {
+    let pool_size = world.resource::<DioxusUiTemplatePoolSize>().0;
+    match template_instances.remove(&entity) {
+        Some(TemplateInstance {
+            template_name,
+            root_index,
+            entities,
+        }) => templates.get_mut(template_name).unwrap().roots[root_index]
+            .park(entities, pool_size, world),
+        None => DespawnRecursive { entity }.apply(world),
+    }
 }
 
 pub struct BevyTemplate {
-    roots: Box<[BevyTemplateNode]>,
+    template: Template<'static>,
+    roots: Box<[BevyTemplateRoot]>,
 }
 
-enum BevyTemplateNode {
-    Node {
-        style: (Style, BackgroundColor),
-        children: Box<[Self]>,
-    },
-    TextNode(Text),
+/// The entities of a mounted template root, by index in its flattened layout.
+pub struct TemplateInstance {
+    template_name: &'static str,
+    root_index: usize,
+    entities: Box<[Entity]>,
+}
+
+/// A template root flattened depth first, with the root node at index 0, so that all of its
+/// entities can be spawned in one batch per bundle type.
+struct BevyTemplateRoot {
+    len: usize,
+    nodes: Box<[(usize, (NodeBundle, InheritedTextStyle))]>,
+    text_nodes: Box<[(usize, Text)]>,
+    test_ids: Box<[(usize, String)]>,
+    style_attributes: Box<[(usize, StyleAttributes)]>,
+    /// Parent indices with the indices of their children, in order.
+    children: Box<[(usize, Box<[usize]>)]>,
+    /// Removed instances, hidden and unparented until they're reused.
+    pool: Vec<Box<[Entity]>>,
 }
 
 impl BevyTemplate {
-    fn from_dioxus(template: &Template) -> Self {
+    pub fn from_dioxus(
+        template: &Template<'static>,
+        stylesheet: &DioxusStylesheet,
+        theme: &Theme,
+        viewport_size: Vec2,
+    ) -> Self {
         Self {
+            template: *template,
             roots: template
                 .roots
                 .iter()
-                .map(BevyTemplateNode::from_dioxus)
+                .map(|root| BevyTemplateRoot::from_dioxus(root, stylesheet, theme, viewport_size))
                 .collect(),
         }
     }
+
+    /// Rebuilds the template's styles after the stylesheet, theme or viewport size changed,
+    /// keeping parked instances.
+    pub fn restyle(&mut self, stylesheet: &DioxusStylesheet, theme: &Theme, viewport_size: Vec2) {
+        for (root, template_root) in self.roots.iter_mut().zip(self.template.roots) {
+            let pool = mem::take(&mut root.pool);
+            *root = BevyTemplateRoot::from_dioxus(template_root, stylesheet, theme, viewport_size);
+            root.pool = pool;
+        }
+    }
+
+    fn instantiate(&mut self, root_index: usize, world: &mut World) -> (Entity, TemplateInstance) {
+        let entities = self.roots[root_index].instantiate(world);
+        let template_instance = TemplateInstance {
+            template_name: self.template.name,
+            root_index,
+            entities,
+        };
+        (template_instance.entities[0], template_instance)
+    }
+
+    /// Despawns the parked instances, for when the template is replaced or its UiRoot torn down.
+    pub fn despawn_pool(&mut self, world: &mut World) {
+        for root in self.roots.iter_mut() {
+            for entities in root.pool.drain(..) {
+                DespawnRecursive {
+                    entity: entities[0],
+                }
+                .apply(world);
+            }
+        }
+    }
 }
 
-impl BevyTemplateNode {
-    fn from_dioxus(node: &TemplateNode) -> Self {
+impl TemplateInstance {
+    pub fn template_name(&self) -> &'static str {
+        self.template_name
+    }
+}
+
+impl BevyTemplateRoot {
+    fn from_dioxus(
+        root: &TemplateNode,
+        stylesheet: &DioxusStylesheet,
+        theme: &Theme,
+        viewport_size: Vec2,
+    ) -> Self {
+        let mut builder = BevyTemplateRootBuilder {
+            stylesheet,
+            theme,
+            viewport_size,
+            len: 0,
+            nodes: Vec::new(),
+            text_nodes: Vec::new(),
+            test_ids: Vec::new(),
+            style_attributes: Vec::new(),
+            children: Vec::new(),
+        };
+        builder.push(root);
+        Self {
+            len: builder.len,
+            nodes: builder.nodes.into(),
+            text_nodes: builder.text_nodes.into(),
+            test_ids: builder.test_ids.into(),
+            style_attributes: builder.style_attributes.into(),
+            children: builder.children.into(),
+            pool: Vec::new(),
+        }
+    }
+
+    fn instantiate(&mut self, world: &mut World) -> Box<[Entity]> {
+        match self.pool.pop() {
+            Some(mut entities) => {
+                self.reset(&mut entities, world);
+                entities
+            }
+            None => self.spawn(world),
+        }
+    }
+
+    fn spawn(&self, world: &mut World) -> Box<[Entity]> {
+        let mut entities = vec![Entity::PLACEHOLDER; self.len];
+
+        let node_entities = world.spawn_batch(self.nodes.iter().map(|(_, node)| node.clone()));
+        for ((index, _), entity) in self.nodes.iter().zip(node_entities) {
+            entities[*index] = entity;
+        }
+
+        let text_entities = world.spawn_batch(self.text_nodes.iter().map(|(_, text)| TextBundle {
+            text: text.clone(),
+            ..default()
+        }));
+        for ((index, _), entity) in self.text_nodes.iter().zip(text_entities) {
+            entities[*index] = entity;
+        }
+
+        for (index, test_id) in self.test_ids.iter() {
+            world
+                .entity_mut(entities[*index])
+                .insert(DioxusUiTestId(test_id.clone()));
+        }
+        for (index, style_attributes) in self.style_attributes.iter() {
+            world
+                .entity_mut(entities[*index])
+                .insert(style_attributes.clone());
+        }
+
+        let mut child_entities = Vec::new();
+        for (parent, children) in self.children.iter() {
+            child_entities.clear();
+            child_entities.extend(children.iter().map(|child| entities[*child]));
+            world
+                .entity_mut(entities[*parent])
+                .push_children(&child_entities);
+        }
+
+        entities.into()
+    }
+
+    /// Despawns the instance instead once the pool is full, see DioxusUiTemplatePoolSize.
+    fn park(&mut self, entities: Box<[Entity]>, pool_size: usize, world: &mut World) {
+        if self.pool.len() >= pool_size {
+            DespawnRecursive {
+                entity: entities[0],
+            }
+            .apply(world);
+            return;
+        }
+
+        let mut root = world.entity_mut(entities[0]);
+        root.remove_parent().insert(Visibility::Hidden);
+        if let Some(mut style) = root.get_mut::<Style>() {
+            style.display = Display::None;
+        }
+        self.pool.push(entities);
+    }
+
+    /// Restores a parked instance to the template's initial state. Dynamic nodes are replaced by
+    /// fresh placeholders, and placeholders that were replaced are respawned.
+    fn reset(&self, entities: &mut [Entity], world: &mut World) {
+        for (index, node) in self.nodes.iter() {
+            match world.get_entity_mut(entities[*index]) {
+                Some(mut entity) => {
+                    entity.insert(node.clone()).remove::<DioxusUiTestId>();
+                }
+                None => entities[*index] = world.spawn(node.clone()).id(),
+            }
+        }
+        for (index, text) in self.text_nodes.iter() {
+            let text_bundle = TextBundle {
+                text: text.clone(),
+                ..default()
+            };
+            match world.get_entity_mut(entities[*index]) {
+                Some(mut entity) => {
+                    entity.insert(text_bundle).remove::<DioxusUiTestId>();
+                }
+                None => entities[*index] = world.spawn(text_bundle).id(),
+            }
+        }
+
+        for (index, test_id) in self.test_ids.iter() {
+            world
+                .entity_mut(entities[*index])
+                .insert(DioxusUiTestId(test_id.clone()));
+        }
+        for (index, style_attributes) in self.style_attributes.iter() {
+            world
+                .entity_mut(entities[*index])
+                .insert(style_attributes.clone());
+        }
+
+        let mut child_entities = Vec::new();
+        for (parent, children) in self.children.iter() {
+            child_entities.clear();
+            child_entities.extend(children.iter().map(|child| entities[*child]));
+
+            let stale_children = world
+                .get::<Children>(entities[*parent])
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|child| !child_entities.contains(child))
+                .collect::<Vec<_>>();
+            for entity in stale_children {
+                DespawnRecursive { entity }.apply(world);
+            }
+
+            world
+                .entity_mut(entities[*parent])
+                .push_children(&child_entities);
+        }
+    }
+}
+
+struct BevyTemplateRootBuilder<'a> {
+    stylesheet: &'a DioxusStylesheet,
+    theme: &'a Theme,
+    viewport_size: Vec2,
+    len: usize,
+    nodes: Vec<(usize, (NodeBundle, InheritedTextStyle))>,
+    text_nodes: Vec<(usize, Text)>,
+    test_ids: Vec<(usize, String)>,
+    style_attributes: Vec<(usize, StyleAttributes)>,
+    children: Vec<(usize, Box<[usize]>)>,
+}
+
+impl BevyTemplateRootBuilder<'_> {
+    /// Adds `node` and its descendants, returning the index of `node`.
+    fn push(&mut self, node: &TemplateNode) -> usize {
+        let index = self.len;
+        self.len += 1;
+
         match node {
             TemplateNode::Element {
                 tag,
@@ -241,75 +597,170 @@ impl BevyTemplateNode {
                         "Encountered unsupported bevy_dioxus tag `{tag}`. Only `node` is supported."
                     );
                 }
-                Self::Node {
-                    style: parse_style_attributes(attrs),
-                    children: children.iter().map(Self::from_dioxus).collect(),
+                let (node_style, style_attributes) =
+                    parse_style_attributes(attrs, self.stylesheet, self.theme, self.viewport_size);
+                self.nodes.push((index, node_style.into_bundle()));
+                if let Some(test_id) = parse_test_id(attrs) {
+                    self.test_ids.push((index, test_id));
+                }
+                if let Some(style_attributes) = style_attributes {
+                    self.style_attributes.push((index, style_attributes));
+                }
+
+                if !children.is_empty() {
+                    let children = children.iter().map(|child| self.push(child)).collect();
+                    self.children.push((index, children));
                 }
             }
-            TemplateNode::Text { text } => {
-                Self::TextNode(Text::from_section(*text, TextStyle::default()))
-            }
-            TemplateNode::Dynamic { id: _ } => Self::Node {
-                style: (Style::default(), Color::NONE.into()),
-                children: Box::new([]),
-            },
-            TemplateNode::DynamicText { id: _ } => {
-                Self::TextNode(Text::from_section("", TextStyle::default()))
+            TemplateNode::Text { text } => self.text_nodes.push((index, text_section(text))),
+            TemplateNode::Dynamic { id: _ } => {
+                self.nodes.push((index, NodeStyle::default().into_bundle()))
             }
+            TemplateNode::DynamicText { id: _ } => self.text_nodes.push((index, text_section(""))),
         }
+
+        index
     }
+}
 
-    fn spawn(&self, world: &mut World) -> Entity {
-        match self {
-            BevyTemplateNode::Node {
-                style: (style, background_color),
-                children,
-            } => {
-                let children = children
-                    .iter()
-                    .map(|child| child.spawn(world))
-                    .collect::<Box<[_]>>();
-                world
-                    .spawn(NodeBundle {
-                        style: style.clone(),
-                        background_color: background_color.clone(),
-                        ..default()
-                    })
-                    .push_children(&children)
-                    .id()
-            }
-            Self::TextNode(text) => world
-                .spawn(TextBundle {
-                    text: text.clone(),
-                    ..default()
-                })
-                .id(),
+fn text_section(text: &str) -> Text {
+    Text::from_section(text, TextStyle::default())
+}
+
+/// Also returns the node's style attributes if they're needed to restyle it later.
+fn parse_style_attributes(
+    attributes: &[TemplateAttribute],
+    stylesheet: &DioxusStylesheet,
+    theme: &Theme,
+    viewport_size: Vec2,
+) -> (NodeStyle, Option<StyleAttributes>) {
+    let mut style_attributes = StyleAttributes::default();
+    let mut has_dynamic_attributes = false;
+    for attribute in attributes {
+        match attribute {
+            TemplateAttribute::Static {
+                name: TEST_ID_ATTRIBUTE,
+                ..
+            } => {}
+            TemplateAttribute::Static {
+                name,
+                value,
+                namespace: _,
+            } => style_attributes.set(name, value),
+            TemplateAttribute::Dynamic { .. } => has_dynamic_attributes = true,
         }
     }
+
+    let node_style = style_attributes.resolve(stylesheet, theme, viewport_size);
+    let needs_restyling = has_dynamic_attributes
+        || !style_attributes.class.is_empty()
+        || style_attributes.uses_theme_tokens()
+        || style_attributes.uses_viewport_units();
+    (node_style, needs_restyling.then_some(style_attributes))
 }
 
-fn parse_style_attributes(attributes: &[TemplateAttribute]) -> (Style, BackgroundColor) {
-    let mut style = Style::default();
-    let mut background_color = Color::NONE.into();
-    for attribute in attributes {
-        if let TemplateAttribute::Static {
-            name,
+pub(crate) const TEST_ID_ATTRIBUTE: &str = "data-testid";
+pub(crate) const CLASS_ATTRIBUTE: &str = "class";
+
+fn parse_test_id(attributes: &[TemplateAttribute]) -> Option<String> {
+    attributes.iter().find_map(|attribute| match attribute {
+        TemplateAttribute::Static {
+            name: TEST_ID_ATTRIBUTE,
             value,
             namespace: _,
-        } = attribute
-        {
-            set_style_attribute(name, value, &mut style, &mut background_color);
+        } => Some(value.to_string()),
+        _ => None,
+    })
+}
+
+/// Everything the style attributes of a node resolve to.
+#[derive(Clone, PartialEq)]
+pub(crate) struct NodeStyle {
+    pub style: Style,
+    pub background_color: BackgroundColor,
+    pub border_color: BorderColor,
+    pub text_style: InheritedTextStyle,
+}
+
+impl Default for NodeStyle {
+    fn default() -> Self {
+        Self {
+            style: Style::default(),
+            background_color: Color::NONE.into(),
+            border_color: Color::NONE.into(),
+            text_style: InheritedTextStyle::default(),
+        }
+    }
+}
+
+impl NodeStyle {
+    fn get(entity: &EntityWorldMut) -> Self {
+        let default = Self::default();
+        Self {
+            style: entity.get().cloned().unwrap_or(default.style),
+            background_color: entity.get().copied().unwrap_or(default.background_color),
+            border_color: entity.get().copied().unwrap_or(default.border_color),
+            text_style: entity.get().copied().unwrap_or(default.text_style),
+        }
+    }
+
+    /// Only writes the components that changed, so that e.g. changing a color doesn't relayout the
+    /// UI.
+    pub fn apply(self, entity: &mut EntityWorldMut) {
+        set_component(entity, self.style);
+        set_component(entity, self.background_color);
+        set_component(entity, self.border_color);
+        set_component(entity, self.text_style);
+    }
+
+    fn into_bundle(self) -> (NodeBundle, InheritedTextStyle) {
+        (
+            NodeBundle {
+                style: self.style,
+                background_color: self.background_color,
+                border_color: self.border_color,
+                ..default()
+            },
+            self.text_style,
+        )
+    }
+}
+
+fn set_component<T: Component + PartialEq>(entity: &mut EntityWorldMut, value: T) {
+    match entity.get_mut::<T>() {
+        Some(mut component) => {
+            component.set_if_neq(value);
+        }
+        None => {
+            entity.insert(value);
         }
     }
-    (style, background_color)
 }
 
-fn set_style_attribute(
+/// Logs unsupported attributes and invalid values instead of applying them, so that a typo doesn't
+/// take down the app.
+pub(crate) fn set_style_attribute(
     name: &str,
     value: &str,
-    style: &mut Style,
-    background_color: &mut BackgroundColor,
+    theme: &Theme,
+    viewport_size: Vec2,
+    node_style: &mut NodeStyle,
 ) {
+    if let Err(error) = try_set_style_attribute(name, value, theme, viewport_size, node_style) {
+        error!("{error}");
+    }
+}
+
+/// Like `set_style_attribute`, but returns unsupported attributes and values as an error message.
+pub(crate) fn try_set_style_attribute(
+    name: &str,
+    value: &str,
+    theme: &Theme,
+    viewport_size: Vec2,
+    node_style: &mut NodeStyle,
+) -> Result<(), String> {
+    let value = theme.resolve(value)?;
+    let style = &mut node_style.style;
     // TODO: The rest of Style
     match (name, value) {
         ("display", "flex") => style.display = Display::Flex,
@@ -318,40 +769,48 @@ fn set_style_attribute(
         ("position", "relative") => style.position_type = PositionType::Relative,
         ("position", "absolute") => style.position_type = PositionType::Absolute,
         ("flex-direction", "column") => style.flex_direction = FlexDirection::Column,
-        ("background-color", hex) => {
-            background_color.0 = Color::hex(hex).expect(&format!(
-                "Encountered unsupported bevy_dioxus hex Color `{hex}`."
-            ))
-        }
-        ("padding", val) => style.padding = UiRect::all(parse_val(val)),
-        ("width", val) => style.width = parse_val(val),
-        ("height", val) => style.height = parse_val(val),
+        ("background-color", color) => node_style.background_color.0 = parse_color(color)?,
+        ("border-color", color) => node_style.border_color.0 = parse_color(color)?,
+        ("color", color) => node_style.text_style.color = Some(parse_color(color)?),
+        ("font-size", px) => node_style.text_style.font_size = Some(parse_px(px, viewport_size)?),
+        ("padding", px) => style.padding = UiRect::all(Val::Px(parse_px(px, viewport_size)?)),
+        ("border-width", px) => style.border = UiRect::all(Val::Px(parse_px(px, viewport_size)?)),
+        ("width", px) => style.width = Val::Px(parse_px(px, viewport_size)?),
+        ("height", px) => style.height = Val::Px(parse_px(px, viewport_size)?),
         ("justify-content", "space-between") => {
             style.justify_content = JustifyContent::SpaceBetween;
         }
         ("align-content", "space-between") => style.align_content = AlignContent::SpaceBetween,
-        _ => panic!("Encountered unsupported bevy_dioxus attribute `{name}: {value}`."),
+        _ => {
+            return Err(format!(
+                "Encountered unsupported bevy_dioxus attribute `{name}: {value}`."
+            ))
+        }
     }
+    Ok(())
 }
 
-fn parse_val(val: &str) -> Val {
+/// `vw` and `vh` are converted to pixels of the root's window, see DioxusUiTargetCamera, since
+/// bevy_ui would resolve them against the primary window. `viewport_size` is already divided by
+/// UiScale, which bevy_ui multiplies pixels by.
+fn parse_px(val: &str, viewport_size: Vec2) -> Result<f32, String> {
     if let Ok(val) = val.parse::<f32>() {
-        return Val::Px(val);
+        return Ok(val);
     }
     if let Some((val, "")) = val.split_once("px") {
         if let Ok(val) = val.parse::<f32>() {
-            return Val::Px(val);
+            return Ok(val);
         }
     }
     if let Some((val, "")) = val.split_once("vw") {
         if let Ok(val) = val.parse::<f32>() {
-            return Val::Vw(val);
+            return Ok(val / 100.0 * viewport_size.x);
         }
     }
     if let Some((val, "")) = val.split_once("vh") {
         if let Ok(val) = val.parse::<f32>() {
-            return Val::Vh(val);
+            return Ok(val / 100.0 * viewport_size.y);
         }
     }
-    panic!("Encountered unsupported bevy_dioxus Val `{val}`.");
+    Err(format!("Encountered unsupported bevy_dioxus Val `{val}`."))
 }
@@ -0,0 +1,185 @@
+use crate::hooks::read_only_query;
+use bevy::ecs::{
+    query::{QueryState, ReadOnlyWorldQuery},
+    system::{IntoSystem, Query, Resource, RunSystemOnce},
+    world::World,
+};
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+type WorldRequest = Box<dyn FnOnce(&mut World) + Send>;
+
+#[derive(Resource, Default)]
+pub struct AsyncWorldQueue(Arc<Mutex<Vec<WorldRequest>>>);
+
+impl AsyncWorldQueue {
+    pub fn handle(&self) -> AsyncWorld {
+        AsyncWorld {
+            queue: Arc::clone(&self.0),
+        }
+    }
+
+    pub fn take(&self) -> Vec<WorldRequest> {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Gives futures spawned from dioxus components access to the World.
+///
+/// Every request resolves during the next `DioxusUiSet::RunDeferredSystems`, with exclusive access
+/// to the World.
+#[derive(Clone)]
+pub struct AsyncWorld {
+    queue: Arc<Mutex<Vec<WorldRequest>>>,
+}
+
+impl AsyncWorld {
+    pub fn run<R, F>(&self, f: F) -> WorldAccess<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut World) -> R + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(WorldAccessState {
+            result: None,
+            waker: None,
+        }));
+
+        let request_state = Arc::clone(&state);
+        self.queue.lock().unwrap().push(Box::new(move |world| {
+            let result = f(world);
+            let mut state = request_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }));
+
+        WorldAccess { state }
+    }
+
+    pub fn run_system<S, R, M>(&self, system: S) -> WorldAccess<R>
+    where
+        S: IntoSystem<(), R, M> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.run(move |world| world.run_system_once(system))
+    }
+
+    pub fn query<Q, F, R>(
+        &self,
+        f: impl FnOnce(Query<Q, F>) -> R + Send + 'static,
+    ) -> WorldAccess<R>
+    where
+        Q: ReadOnlyWorldQuery + 'static,
+        F: ReadOnlyWorldQuery + 'static,
+        R: Send + 'static,
+    {
+        self.run(move |world| {
+            let query_state = QueryState::<Q, F>::new(world);
+            f(read_only_query(&query_state, world))
+        })
+    }
+}
+
+struct WorldAccessState<R> {
+    result: Option<R>,
+    waker: Option<Waker>,
+}
+
+pub struct WorldAccess<R> {
+    state: Arc<Mutex<WorldAccessState<R>>>,
+}
+
+impl<R> Future for WorldAccess<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hooks::use_async_world, prelude::*, testing::DioxusTestApp};
+    use bevy::ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{ResMut, Resource},
+    };
+    use dioxus::prelude::*;
+    use std::sync::Mutex;
+
+    #[derive(Component)]
+    struct Marker;
+
+    #[derive(Resource, Default)]
+    struct Runs(u32);
+
+    #[test]
+    fn resolves_requests_at_the_next_run_deferred_systems() {
+        static STEPS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        fn Requests(cx: Scope) -> Element {
+            let async_world = use_async_world(cx);
+            render! {
+                node {
+                    data_testid: "start",
+                    onclick: move |_| {
+                        let async_world = async_world.clone();
+                        async move {
+                            async_world.run(|world| world.spawn(Marker).id()).await;
+                            STEPS.lock().unwrap().push("spawned".to_owned());
+
+                            let markers = async_world
+                                .query::<Entity, With<Marker>, _>(|markers| markers.iter().count())
+                                .await;
+                            STEPS.lock().unwrap().push(format!("queried {markers}"));
+
+                            async_world
+                                .run_system(|mut runs: ResMut<Runs>| runs.0 += 1)
+                                .await;
+                            STEPS.lock().unwrap().push("ran system".to_owned());
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Requests);
+        app.world_mut().init_resource::<Runs>();
+        let steps = || STEPS.lock().unwrap().clone();
+
+        // The future starts and queues its first request, which waits for the next frame
+        let start = app.find_by_test_id("start").unwrap();
+        app.click(start);
+        assert!(steps().is_empty());
+        let world = app.world_mut();
+        assert_eq!(world.query::<&Marker>().iter(world).count(), 0);
+
+        app.update();
+        assert_eq!(steps(), ["spawned"]);
+        let world = app.world_mut();
+        assert_eq!(world.query::<&Marker>().iter(world).count(), 1);
+
+        app.update();
+        assert_eq!(steps(), ["spawned", "queried 1"]);
+        assert_eq!(app.world().resource::<Runs>().0, 0);
+
+        app.update();
+        assert_eq!(steps(), ["spawned", "queried 1", "ran system"]);
+        assert_eq!(app.world().resource::<Runs>().0, 1);
+    }
+}
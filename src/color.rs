@@ -0,0 +1,368 @@
+use bevy::render::color::Color;
+
+/// Parses a CSS color: `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()`,
+/// `hsla()` or a named color. Every color attribute should be parsed with this.
+///
+/// Functions take comma or space separated channels, with an optional alpha after the channels or
+/// a `/`, like `rgb(79 70 229 / 50%)`.
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    let color = value.trim().to_ascii_lowercase();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        // Color::hex would strip a second `#` as well
+        return Color::hex(hex)
+            .ok()
+            .filter(|_| !hex.starts_with('#'))
+            .ok_or_else(|| invalid_color(value, "expected #rgb, #rgba, #rrggbb or #rrggbbaa"));
+    }
+
+    if let Some((function, arguments)) = color.split_once('(') {
+        return parse_color_function(function.trim_end(), arguments)
+            .map_err(|reason| invalid_color(value, reason));
+    }
+
+    if color == "transparent" {
+        return Ok(Color::NONE);
+    }
+    if let Some((_, rgb)) = NAMED_COLORS.iter().find(|(name, _)| *name == color) {
+        return Ok(Color::rgb_u8(
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            *rgb as u8,
+        ));
+    }
+
+    Err(invalid_color(
+        value,
+        "expected a #hex color, rgb(), rgba(), hsl(), hsla() or a named color",
+    ))
+}
+
+fn parse_color_function(function: &str, arguments: &str) -> Result<Color, String> {
+    let Some(arguments) = arguments.strip_suffix(')') else {
+        return Err("missing `)`".to_owned());
+    };
+    let (channels, alpha) = split_channels(arguments)?;
+    let alpha = match alpha {
+        Some(alpha) => parse_alpha(alpha)?,
+        None => 1.0,
+    };
+
+    match function {
+        "rgb" | "rgba" => Ok(Color::rgba(
+            parse_rgb_channel(channels[0])?,
+            parse_rgb_channel(channels[1])?,
+            parse_rgb_channel(channels[2])?,
+            alpha,
+        )),
+        "hsl" | "hsla" => Ok(Color::hsla(
+            parse_hue(channels[0])?,
+            parse_percentage(channels[1])?,
+            parse_percentage(channels[2])?,
+            alpha,
+        )),
+        function => Err(format!("unsupported function `{function}()`")),
+    }
+}
+
+fn invalid_color(value: &str, reason: impl AsRef<str>) -> String {
+    format!(
+        "Encountered invalid bevy_dioxus color `{value}`: {}.",
+        reason.as_ref()
+    )
+}
+
+/// Splits function arguments into three channels and an optional alpha.
+fn split_channels(arguments: &str) -> Result<([&str; 3], Option<&str>), String> {
+    let (channels, alpha) = match arguments.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (arguments, None),
+    };
+
+    let mut channels = channels
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|channel| !channel.is_empty())
+        .collect::<Vec<_>>();
+    let alpha = match (alpha, channels.len()) {
+        (None, 4) => channels.pop(),
+        (alpha, _) => alpha,
+    };
+
+    match channels[..] {
+        [a, b, c] => Ok(([a, b, c], alpha)),
+        _ => Err(format!(
+            "expected 3 channels and an optional alpha, found {} channels",
+            channels.len()
+        )),
+    }
+}
+
+/// A number from 0 to 255, or a percentage.
+fn parse_rgb_channel(channel: &str) -> Result<f32, String> {
+    match channel.strip_suffix('%') {
+        Some(percentage) => Ok(parse_number(percentage)? / 100.0),
+        None => Ok(parse_number(channel)? / 255.0),
+    }
+    .map(|channel| channel.clamp(0.0, 1.0))
+}
+
+/// Degrees, with an optional `deg` or `turn` unit.
+fn parse_hue(hue: &str) -> Result<f32, String> {
+    let degrees = match (hue.strip_suffix("deg"), hue.strip_suffix("turn")) {
+        (Some(degrees), _) => parse_number(degrees)?,
+        (_, Some(turns)) => parse_number(turns)? * 360.0,
+        (None, None) => parse_number(hue)?,
+    };
+    Ok(degrees.rem_euclid(360.0))
+}
+
+/// A percentage, the `%` is optional.
+fn parse_percentage(percentage: &str) -> Result<f32, String> {
+    let percentage = percentage.strip_suffix('%').unwrap_or(percentage);
+    Ok((parse_number(percentage)? / 100.0).clamp(0.0, 1.0))
+}
+
+/// A number from 0 to 1, or a percentage.
+fn parse_alpha(alpha: &str) -> Result<f32, String> {
+    match alpha.strip_suffix('%') {
+        Some(percentage) => Ok(parse_number(percentage)? / 100.0),
+        None => parse_number(alpha),
+    }
+    .map(|alpha| alpha.clamp(0.0, 1.0))
+}
+
+fn parse_number(number: &str) -> Result<f32, String> {
+    number
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("`{number}` is not a number"))
+}
+
+/// The CSS named colors, except `transparent`.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::parse_color;
+    use bevy::render::color::Color;
+
+    fn assert_color(value: &str, expected: Color) {
+        let color = parse_color(value).unwrap().as_rgba_f32();
+        let expected = expected.as_rgba_f32();
+        assert!(
+            color
+                .iter()
+                .zip(expected)
+                .all(|(channel, expected)| (channel - expected).abs() < 1e-4),
+            "{value}: {color:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_color("#f00", Color::RED);
+        assert_color("#f008", Color::rgba_u8(255, 0, 0, 0x88));
+        assert_color("#4f46e5", Color::rgb_u8(79, 70, 229));
+        assert_color("#4F46E580", Color::rgba_u8(79, 70, 229, 0x80));
+        assert_color("  #4f46e5 ", Color::rgb_u8(79, 70, 229));
+    }
+
+    #[test]
+    fn parses_color_functions() {
+        let indigo = Color::rgb_u8(79, 70, 229);
+        assert_color("rgb(79, 70, 229)", indigo);
+        assert_color("rgb(79 70 229 / 50%)", indigo.with_a(0.5));
+        assert_color("rgb(100%, 0%, 0%)", Color::RED);
+        assert_color("rgba(79, 70, 229, 0.5)", indigo.with_a(0.5));
+        assert_color("RGBA(79 70 229 0.5)", indigo.with_a(0.5));
+        assert_color("hsl(0, 100%, 50%)", Color::RED);
+        assert_color("hsl(120deg 100% 50%)", Color::GREEN);
+        assert_color("hsl(0.5turn 100% 50%)", Color::CYAN);
+        assert_color("hsla(240, 100%, 50%, 0.5)", Color::BLUE.with_a(0.5));
+        assert_color("hsla(240 100% 50% / 50%)", Color::BLUE.with_a(0.5));
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert_color("red", Color::RED);
+        assert_color("RebeccaPurple", Color::rgb_u8(102, 51, 153));
+        assert_eq!(parse_color("transparent"), Ok(Color::NONE));
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for value in [
+            "",
+            "f00",
+            "4f46e5",
+            "##f00",
+            "#ff",
+            "#fffff",
+            "#ggg",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3",
+            "rgb(1, 2, 3, 4, 5)",
+            "rgb(red, 0, 0)",
+            "hsl(0, 100%)",
+            "cmyk(0, 0, 0, 0)",
+            "notacolor",
+        ] {
+            let error = parse_color(value).unwrap_err();
+            assert!(
+                error.starts_with("Encountered invalid bevy_dioxus color"),
+                "{value}: {error}"
+            );
+        }
+    }
+}
@@ -1,3 +0,0 @@
-pub const NEUTRAL_800: &'static str = "#27272a";
-
-pub const INDIGO_600: &'static str = "#4f46e5";
@@ -0,0 +1,223 @@
+use bevy::tasks::{block_on, futures_lite::future::poll_once, AsyncComputeTaskPool, Task};
+use std::{
+    cell::RefCell,
+    future::Future,
+    rc::{Rc, Weak},
+    sync::Arc,
+};
+
+/// Polls every running `use_task` once per frame. A poller returns false once it's done.
+pub(crate) type ComputeTasks = Rc<RefCell<Vec<Box<dyn FnMut() -> bool>>>>;
+
+pub enum TaskState<T, E> {
+    Pending,
+    Ready(T),
+    Error(E),
+}
+
+/// Dropping this cancels the task, so it's owned by the hook and only weakly referenced by its poller.
+pub(crate) struct ComputeTask<T, E> {
+    task: Option<Task<Result<T, E>>>,
+    output: Option<Result<T, E>>,
+}
+
+impl<T, E> ComputeTask<T, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    pub fn spawn(
+        future: impl Future<Output = Result<T, E>> + Send + 'static,
+        schedule_update: Arc<dyn Fn() + Send + Sync>,
+        compute_tasks: &ComputeTasks,
+    ) -> Rc<RefCell<Self>> {
+        let compute_task = Rc::new(RefCell::new(Self {
+            task: Some(AsyncComputeTaskPool::get().spawn(future)),
+            output: None,
+        }));
+
+        let weak_compute_task = Rc::downgrade(&compute_task);
+        compute_tasks.borrow_mut().push(Box::new(move || {
+            poll(&weak_compute_task, &*schedule_update)
+        }));
+
+        compute_task
+    }
+
+    pub fn take_output(&mut self) -> Option<Result<T, E>> {
+        self.output.take()
+    }
+}
+
+fn poll<T, E>(compute_task: &Weak<RefCell<ComputeTask<T, E>>>, schedule_update: &dyn Fn()) -> bool {
+    let Some(compute_task) = compute_task.upgrade() else {
+        return false;
+    };
+    let mut compute_task = compute_task.borrow_mut();
+    let Some(task) = &mut compute_task.task else {
+        return false;
+    };
+
+    match block_on(poll_once(task)) {
+        Some(output) => {
+            compute_task.task = None;
+            compute_task.output = Some(output);
+            schedule_update();
+            false
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compute_task::TaskState, hooks::use_task, prelude::*, testing::DioxusTestApp, UiContext,
+    };
+    use dioxus::prelude::*;
+    use std::{
+        future::poll_fn,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Mutex,
+        },
+        task::{Poll, Waker},
+        thread,
+        time::Duration,
+    };
+
+    /// A task output that the test hands to the task once it's ready.
+    struct Gate(Mutex<(Option<Result<u32, String>>, Option<Waker>)>);
+
+    impl Gate {
+        const fn new() -> Self {
+            Self(Mutex::new((None, None)))
+        }
+
+        fn open(&self, output: Result<u32, String>) {
+            let mut state = self.0.lock().unwrap();
+            state.0 = Some(output);
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        }
+
+        async fn wait(&'static self) -> Result<u32, String> {
+            poll_fn(|cx| {
+                let mut state = self.0.lock().unwrap();
+                match state.0.take() {
+                    Some(output) => Poll::Ready(output),
+                    None => {
+                        state.1 = Some(cx.waker().clone());
+                        Poll::Pending
+                    }
+                }
+            })
+            .await
+        }
+    }
+
+    fn state_text(task_state: &TaskState<u32, String>) -> String {
+        match task_state {
+            TaskState::Pending => "Pending".to_owned(),
+            TaskState::Ready(value) => format!("Ready {value}"),
+            TaskState::Error(error) => format!("Error {error}"),
+        }
+    }
+
+    /// Tasks finish on another thread, so this runs frames until `done` or a timeout.
+    fn update_until(app: &mut DioxusTestApp, done: impl Fn(&DioxusTestApp) -> bool) {
+        for _ in 0..1000 {
+            if done(app) {
+                return;
+            }
+            app.update();
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Timed out waiting for the task");
+    }
+
+    #[test]
+    fn rerenders_with_the_task_output() {
+        static READY: Gate = Gate::new();
+        static FAILED: Gate = Gate::new();
+
+        fn Tasks(cx: Scope) -> Element {
+            let ready = state_text(use_task(cx, || READY.wait()));
+            let failed = state_text(use_task(cx, || FAILED.wait()));
+            render! {
+                node { "{ready}" }
+                node { "{failed}" }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Tasks);
+        app.update_frames(3);
+        assert!(app.find_by_text("Pending").is_some());
+
+        READY.open(Ok(5));
+        update_until(&mut app, |app| app.find_by_text("Ready 5").is_some());
+        assert!(app.find_by_text("Pending").is_some());
+
+        FAILED.open(Err("boom".to_owned()));
+        update_until(&mut app, |app| app.find_by_text("Error boom").is_some());
+        assert!(app.find_by_text("Ready 5").is_some());
+    }
+
+    #[test]
+    fn cancels_the_task_when_the_component_is_destroyed() {
+        static NEVER: Gate = Gate::new();
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+
+        struct SetOnDrop(&'static AtomicBool);
+
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        fn Toggle(cx: Scope) -> Element {
+            let shown = use_state(cx, || true);
+            render! {
+                node { data_testid: "hide", onclick: move |_| shown.set(false) }
+                if **shown {
+                    rsx! { Waiting {} }
+                }
+            }
+        }
+
+        fn Waiting(cx: Scope) -> Element {
+            use_task(cx, || {
+                // Created outside of the async block, so it's dropped with the future even if the
+                // future never ran
+                let set_on_drop = SetOnDrop(&DROPPED);
+                async move {
+                    let _set_on_drop = set_on_drop;
+                    NEVER.wait().await
+                }
+            });
+            render! { "Waiting" }
+        }
+
+        let compute_tasks = |app: &DioxusTestApp| {
+            app.world()
+                .non_send_resource::<UiContext>()
+                .compute_tasks
+                .borrow()
+                .len()
+        };
+
+        let mut app = DioxusTestApp::new(Toggle);
+        assert_eq!(compute_tasks(&app), 1);
+        assert!(!DROPPED.load(Ordering::SeqCst));
+
+        let hide = app.find_by_test_id("hide").unwrap();
+        app.click(hide);
+        assert!(app.find_by_text("Waiting").is_none());
+        // The poller notices that its task is gone at the next RunDeferredSystems
+        app.update();
+        assert_eq!(compute_tasks(&app), 0);
+        update_until(&mut app, |_| DROPPED.load(Ordering::SeqCst));
+    }
+}
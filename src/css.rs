@@ -0,0 +1,343 @@
+use crate::{
+    apply_mutations::{try_set_style_attribute, NodeStyle},
+    DioxusStylesheet, Theme,
+};
+use bevy::{
+    asset::{io::Reader, AssetEvent, AssetLoader, Assets, AsyncReadExt, Handle, LoadContext},
+    ecs::{
+        event::EventReader,
+        system::{Res, ResMut, Resource},
+    },
+    math::Vec2,
+    utils::BoxedFuture,
+};
+use std::{error::Error, fmt, io, string::FromUtf8Error};
+
+/// Replaces the DioxusStylesheet resource with this stylesheet whenever it's loaded or modified, so
+/// edits to the `.css` file restyle the UI while the app is running.
+#[derive(Resource, Clone)]
+pub struct DioxusStylesheetHandle(pub Handle<DioxusStylesheet>);
+
+/// Loads `.css` files as DioxusStylesheets.
+///
+/// Supports a subset of CSS: rules with comma separated class selectors, and declarations of
+/// the same attributes as bevy_dioxus nodes. Other selectors, like pseudo-classes, are errors.
+#[derive(Default)]
+pub struct CssLoader;
+
+impl AssetLoader for CssLoader {
+    type Asset = DioxusStylesheet;
+    type Settings = ();
+    type Error = CssError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<DioxusStylesheet, CssError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            DioxusStylesheet::from_css(&String::from_utf8(bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["css"]
+    }
+}
+
+#[derive(Debug)]
+pub enum CssError {
+    Io(io::Error),
+    Utf8(FromUtf8Error),
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for CssError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Failed to read stylesheet: {error}"),
+            Self::Utf8(error) => write!(f, "Stylesheet is not valid UTF-8: {error}"),
+            Self::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "{line}:{column}: {message}"),
+        }
+    }
+}
+
+impl Error for CssError {}
+
+impl From<io::Error> for CssError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<FromUtf8Error> for CssError {
+    fn from(error: FromUtf8Error) -> Self {
+        Self::Utf8(error)
+    }
+}
+
+impl DioxusStylesheet {
+    /// Parses the subset of CSS described on CssLoader.
+    pub fn from_css(source: &str) -> Result<Self, CssError> {
+        let source = blank_out_comments(source)?;
+        let mut stylesheet = Self::default();
+        let theme = Theme::default();
+
+        let mut offset = 0;
+        loop {
+            offset += leading_whitespace(&source[offset..]);
+            if offset == source.len() {
+                return Ok(stylesheet);
+            }
+
+            let block_start = match source[offset..].find(['{', '}']) {
+                Some(index) if source[offset + index..].starts_with('{') => offset + index,
+                Some(index) => return Err(syntax_error(&source, offset + index, "Unexpected `}`")),
+                None => return Err(syntax_error(&source, offset, "Expected `{` after selector")),
+            };
+            let block_end = match source[block_start + 1..].find(['{', '}']) {
+                Some(index) if source[block_start + 1 + index..].starts_with('}') => {
+                    block_start + 1 + index
+                }
+                _ => return Err(syntax_error(&source, block_start, "Unterminated rule")),
+            };
+
+            let classes = parse_selectors(&source, offset, block_start)?;
+            let declarations = parse_declarations(&source, block_start + 1, block_end, &theme)?;
+            for class in classes {
+                stylesheet.extend_class(class, declarations.iter().cloned());
+            }
+
+            offset = block_end + 1;
+        }
+    }
+}
+
+/// Applies the stylesheet in DioxusStylesheetHandle once it's loaded and after every change.
+pub fn sync_stylesheet_asset(
+    stylesheet_handle: Option<Res<DioxusStylesheetHandle>>,
+    mut asset_events: EventReader<AssetEvent<DioxusStylesheet>>,
+    stylesheet_assets: Res<Assets<DioxusStylesheet>>,
+    mut stylesheet: ResMut<DioxusStylesheet>,
+) {
+    let Some(stylesheet_handle) = stylesheet_handle else {
+        asset_events.clear();
+        return;
+    };
+
+    let asset_changed = asset_events.read().any(|asset_event| {
+        asset_event.is_loaded_with_dependencies(&stylesheet_handle.0)
+            || asset_event.is_modified(&stylesheet_handle.0)
+    });
+    if asset_changed || stylesheet_handle.is_changed() {
+        if let Some(stylesheet_asset) = stylesheet_assets.get(&stylesheet_handle.0) {
+            *stylesheet = stylesheet_asset.clone();
+        }
+    }
+}
+
+/// Returns the classes of a comma separated list of class selectors in `source[start..end]`.
+fn parse_selectors(source: &str, start: usize, end: usize) -> Result<Vec<String>, CssError> {
+    let mut classes = Vec::new();
+    for (offset, selector) in split_with_offsets(source, start, end, ',') {
+        let class = selector
+            .strip_prefix('.')
+            .filter(|class| {
+                !class.is_empty()
+                    && class
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            })
+            .ok_or_else(|| {
+                syntax_error(
+                    source,
+                    offset,
+                    format!(
+                        "Unsupported selector `{selector}`, only class selectors are supported"
+                    ),
+                )
+            })?;
+        classes.push(class.to_owned());
+    }
+    Ok(classes)
+}
+
+/// Returns the `name: value` declarations separated by `;` in `source[start..end]`, checking that
+/// bevy_dioxus supports each of them. Theme tokens are checked with their value in `theme`.
+fn parse_declarations(
+    source: &str,
+    start: usize,
+    end: usize,
+    theme: &Theme,
+) -> Result<Vec<(String, String)>, CssError> {
+    let mut declarations = Vec::new();
+    for (offset, declaration) in split_with_offsets(source, start, end, ';') {
+        // A trailing `;` is optional
+        if declaration.is_empty() {
+            continue;
+        }
+
+        let Some((name, value)) = declaration.split_once(':') else {
+            return Err(syntax_error(
+                source,
+                offset,
+                format!("Expected `name: value`, found `{declaration}`"),
+            ));
+        };
+        let (name, value) = (name.trim(), value.trim());
+        match try_set_style_attribute(name, value, theme, Vec2::ZERO, &mut NodeStyle::default()) {
+            Ok(()) => {}
+            // Tokens of a custom theme without a fallback are only known when the stylesheet is
+            // applied, which logs them if they're unknown
+            Err(_) if is_custom_token(value, theme) => {}
+            Err(message) => return Err(syntax_error(source, offset, message)),
+        }
+
+        declarations.push((name.to_owned(), value.to_owned()));
+    }
+    Ok(declarations)
+}
+
+/// Whether `value` is a `var(--token)` without a fallback for a token that's not in `theme`.
+fn is_custom_token(value: &str, theme: &Theme) -> bool {
+    value
+        .strip_prefix("var(--")
+        .and_then(|name| name.strip_suffix(')'))
+        .is_some_and(|name| !name.contains(',') && theme.get_token(name.trim()).is_none())
+}
+
+/// Splits `source[start..end]` at `separator`, returning each trimmed part with its offset in
+/// `source`.
+fn split_with_offsets(
+    source: &str,
+    start: usize,
+    end: usize,
+    separator: char,
+) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = start;
+    source[start..end].split(separator).map(move |part| {
+        let part_offset = offset + leading_whitespace(part);
+        offset += part.len() + separator.len_utf8();
+        (part_offset, part.trim())
+    })
+}
+
+fn leading_whitespace(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+/// Replaces comments with spaces, keeping line breaks, so that offsets still point at the same
+/// line and column.
+fn blank_out_comments(source: &str) -> Result<String, CssError> {
+    let mut blanked = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(comment_start) = rest.find("/*") {
+        blanked.push_str(&rest[..comment_start]);
+        let Some(comment_length) = rest[comment_start + 2..].find("*/") else {
+            let offset = source.len() - rest.len() + comment_start;
+            return Err(syntax_error(source, offset, "Unterminated comment"));
+        };
+        let comment = &rest[comment_start..comment_start + 2 + comment_length + 2];
+        blanked.extend(comment.chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+        rest = &rest[comment_start + comment.len()..];
+    }
+    blanked.push_str(rest);
+    Ok(blanked)
+}
+
+fn syntax_error(source: &str, offset: usize, message: impl Into<String>) -> CssError {
+    let before = &source[..offset];
+    CssError::Syntax {
+        line: before.matches('\n').count() + 1,
+        column: before.rsplit('\n').next().unwrap().chars().count() + 1,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{css::CssError, DioxusStylesheet};
+
+    fn syntax_error(source: &str) -> (usize, usize, String) {
+        match DioxusStylesheet::from_css(source) {
+            Err(CssError::Syntax {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            Err(error) => panic!("Expected a syntax error, got `{error}`."),
+            Ok(_) => panic!("Expected a syntax error."),
+        }
+    }
+
+    #[test]
+    fn parses_rules_and_theme_tokens() {
+        let stylesheet = DioxusStylesheet::from_css(
+            "/* Cards */
+            .card, .panel { padding: 8px; background-color: var(--neutral-800) }
+            .title { color: var(--brand, #ff0000); font-size: var(--text-lg); }
+            .custom { width: var(--sidebar-width) }",
+        )
+        .unwrap();
+
+        let declarations = |class| {
+            stylesheet
+                .get_class(class)
+                .unwrap()
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect::<Vec<_>>()
+        };
+        let card = [
+            ("padding", "8px"),
+            ("background-color", "var(--neutral-800)"),
+        ];
+        assert_eq!(declarations("card"), card);
+        assert_eq!(declarations("panel"), card);
+        assert_eq!(
+            declarations("title"),
+            [
+                ("color", "var(--brand, #ff0000)"),
+                ("font-size", "var(--text-lg)")
+            ]
+        );
+        assert_eq!(declarations("custom"), [("width", "var(--sidebar-width)")]);
+        assert_eq!(stylesheet.classes().count(), 4);
+    }
+
+    #[test]
+    fn reports_unsupported_selectors() {
+        let (line, column, message) = syntax_error(".card {}\n.card, div:hover { width: 8 }");
+        assert_eq!((line, column), (2, 8));
+        assert!(message.contains("`div:hover`"), "{message}");
+    }
+
+    #[test]
+    fn reports_unterminated_rules() {
+        let (line, column, _) = syntax_error(".card { width: 8 }\n  .panel { width: 8");
+        assert_eq!((line, column), (2, 10));
+        let (line, column, _) = syntax_error(".card { width: 8 /* }");
+        assert_eq!((line, column), (1, 18));
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        let (line, column, _) = syntax_error(".card {\n  width: 8;\n  height: tall;\n}");
+        assert_eq!((line, column), (3, 3));
+        let (line, column, _) = syntax_error(".card { width: 8; color: var(--spacing-4) }");
+        assert_eq!((line, column), (1, 19));
+        let (line, column, _) = syntax_error(".card { color: var(--brand, #nope) }");
+        assert_eq!((line, column), (1, 9));
+    }
+}
@@ -0,0 +1,131 @@
+use bevy::{
+    ecs::{
+        bundle::Bundle,
+        entity::Entity,
+        system::{Command, CommandQueue, Despawn, Insert, Remove, Resource},
+        world::World,
+    },
+    hierarchy::DespawnRecursive,
+};
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Resource, Default)]
+pub struct DeferredCommandQueue(Arc<Mutex<CommandQueue>>);
+
+impl DeferredCommandQueue {
+    pub fn handle(&self) -> DioxusUiCommands {
+        DioxusUiCommands {
+            queue: Arc::clone(&self.0),
+        }
+    }
+
+    pub fn take(&self) -> CommandQueue {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Records ECS commands from dioxus event handlers.
+///
+/// Commands are applied in the next `DioxusUiSet::RunDeferredSystems`, right after deferred systems run.
+#[derive(Clone)]
+pub struct DioxusUiCommands {
+    queue: Arc<Mutex<CommandQueue>>,
+}
+
+impl DioxusUiCommands {
+    pub fn add<C: Command>(&self, command: C) {
+        self.queue.lock().unwrap().push(command);
+    }
+
+    pub fn spawn<B: Bundle>(&self, bundle: B) {
+        self.add(move |world: &mut World| {
+            world.spawn(bundle);
+        });
+    }
+
+    pub fn despawn(&self, entity: Entity) {
+        self.add(Despawn { entity });
+    }
+
+    pub fn despawn_recursive(&self, entity: Entity) {
+        self.add(DespawnRecursive { entity });
+    }
+
+    pub fn insert<B: Bundle>(&self, entity: Entity, bundle: B) {
+        self.add(Insert { entity, bundle });
+    }
+
+    pub fn remove<B: Bundle>(&self, entity: Entity) {
+        self.add(Remove::<B>::new(entity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hooks::{use_commands, use_root_entity, use_world},
+        prelude::*,
+        testing::DioxusTestApp,
+    };
+    use bevy::ecs::component::Component;
+    use dioxus::prelude::*;
+
+    #[derive(Component)]
+    struct Marker;
+
+    #[derive(Component)]
+    struct Selected;
+
+    #[test]
+    fn applies_commands_before_the_next_render() {
+        fn Commands(cx: Scope) -> Element {
+            let commands = use_commands(cx);
+            let root_entity = use_root_entity(cx);
+            let world = use_world(cx);
+            let markers = world
+                .iter_entities()
+                .filter(|entity| entity.contains::<Marker>())
+                .map(|entity| entity.id())
+                .collect::<Vec<_>>();
+            let marker_count = markers.len();
+            let selected = world.get::<Selected>(root_entity).is_some();
+
+            let (spawn, insert, despawn) = (commands.clone(), commands.clone(), commands);
+            render! {
+                node { data_testid: "spawn", onclick: move |_| spawn.spawn(Marker), "Markers: {marker_count}" }
+                node { data_testid: "insert", onclick: move |_| insert.insert(root_entity, Selected), "Selected: {selected}" }
+                node {
+                    data_testid: "despawn",
+                    onclick: move |_| {
+                        for marker in &markers {
+                            despawn.despawn(*marker);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Commands);
+        let spawn = app.find_by_test_id("spawn").unwrap();
+        // Queues the command, the render in the same frame still sees the old World
+        app.click(spawn);
+        assert!(app.find_by_text("Markers: 0").is_some());
+        app.update();
+        assert!(app.find_by_text("Markers: 1").is_some());
+
+        let click = |app: &mut DioxusTestApp, test_id: &str| {
+            let target = app.find_by_test_id(test_id).unwrap();
+            app.click(target);
+            app.update();
+        };
+
+        click(&mut app, "insert");
+        assert!(app.find_by_text("Selected: true").is_some());
+
+        click(&mut app, "despawn");
+        assert!(app.find_by_text("Markers: 0").is_some());
+    }
+}
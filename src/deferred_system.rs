@@ -1,54 +1,139 @@
-use bevy::{
-    ecs::{
-        system::{IntoSystem, Resource, SystemId},
-        world::World,
-    },
-    utils::HashMap,
+use bevy::ecs::{
+    system::{BoxedSystem, IntoSystem, Resource},
+    world::World,
 };
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+type DeferredSystemSlot = Arc<Mutex<Option<DeferredSystemState>>>;
+
+/// Systems are owned by their `use_system` hook instead of being registered in the World, so that
+/// creating one doesn't mutate the World during a render.
 #[derive(Resource, Default)]
 pub struct DeferredSystemRegistry {
-    // TODO: Replace ref_counts with Box<Vec<SystemId>> and insert SystemId into it on unmount
-    pub ref_counts: HashMap<SystemId, Arc<()>>,
-    pub run_queue: Box<Vec<SystemId>>,
+    pub run_queue: Arc<Mutex<Vec<DeferredSystemSlot>>>,
+    /// Systems whose owning hook was unmounted. Drained after the run queue so that a system scheduled in the
+    /// same frame as its unmount still runs once. Draining drops the system itself, so handles cloned into
+    /// long-lived closures only keep an empty slot alive, and calling them afterwards does nothing.
+    pub removal_queue: Arc<Mutex<Vec<DeferredSystemSlot>>>,
+}
+
+pub struct DeferredSystemState {
+    system: BoxedSystem,
+    initialized: bool,
+}
+
+impl DeferredSystemState {
+    pub fn run(&mut self, world: &mut World) {
+        if !self.initialized {
+            self.system.initialize(world);
+            self.initialized = true;
+        }
+        self.system.run((), world);
+        self.system.apply_deferred(world);
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 struct DeferredSystem {
-    id: SystemId,
-    run_queue: *mut Vec<SystemId>,
+    state: DeferredSystemSlot,
+    run_queue: Arc<Mutex<Vec<DeferredSystemSlot>>>,
+    removal_queue: Arc<Mutex<Vec<DeferredSystemSlot>>>,
 }
 
 impl DeferredSystem {
     fn schedule(&self) {
-        unsafe { &mut *self.run_queue }.push(self.id);
+        self.run_queue.lock().unwrap().push(Arc::clone(&self.state));
     }
-}
 
-unsafe impl Send for DeferredSystem {}
-unsafe impl Sync for DeferredSystem {}
+    fn unregister(&self) {
+        self.removal_queue
+            .lock()
+            .unwrap()
+            .push(Arc::clone(&self.state));
+    }
+}
 
 pub fn new_deferred_system<S, M>(
     system: S,
-    world: &mut World,
-) -> (impl Fn() + Send + Sync + Copy, Arc<()>)
+    system_registry: &DeferredSystemRegistry,
+) -> (
+    impl Fn() + Send + Sync + Clone + 'static,
+    impl Fn() + Send + Sync + Clone + 'static,
+)
 where
     S: IntoSystem<(), (), M> + 'static,
     M: 'static,
 {
-    let id = world.register_system(system);
-    let ref_count = Arc::new(());
+    let deferred_system = DeferredSystem {
+        state: Arc::new(Mutex::new(Some(DeferredSystemState {
+            system: Box::new(IntoSystem::into_system(system)),
+            initialized: false,
+        }))),
+        run_queue: Arc::clone(&system_registry.run_queue),
+        removal_queue: Arc::clone(&system_registry.removal_queue),
+    };
+    let unregister = deferred_system.clone();
 
-    let mut system_registry = world.resource_mut::<DeferredSystemRegistry>();
-    system_registry
-        .ref_counts
-        .insert(id, Arc::clone(&ref_count));
+    (
+        move || deferred_system.schedule(),
+        move || unregister.unregister(),
+    )
+}
 
-    let deferred_system = DeferredSystem {
-        id,
-        run_queue: Box::as_mut(&mut system_registry.run_queue),
+#[cfg(test)]
+mod tests {
+    use crate::{hooks::use_system, testing::DioxusTestApp};
+    use bevy::{
+        ecs::system::{ResMut, Resource},
+        hierarchy::DespawnRecursiveExt,
     };
+    use dioxus::prelude::*;
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    #[derive(Resource, Default)]
+    struct Runs(usize);
 
-    (move || deferred_system.schedule(), ref_count)
+    type Handle = Box<dyn Fn() + Send + Sync>;
+
+    static HANDLE: Mutex<Option<Handle>> = Mutex::new(None);
+    static SYSTEM_TOKEN: OnceLock<Arc<()>> = OnceLock::new();
+
+    fn StoresHandle(cx: Scope) -> Element {
+        let token = Arc::clone(SYSTEM_TOKEN.get_or_init(Default::default));
+        let system = use_system(cx, move |mut runs: ResMut<Runs>| {
+            let _ = &token;
+            runs.0 += 1;
+        });
+        HANDLE
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| Box::new(system.clone()));
+        None
+    }
+
+    /// Handles can outlive their root in closures stored outside of dioxus. Calling one after its
+    /// root is torn down must neither run nor keep the system alive. Also run this under Miri with
+    /// `MIRIFLAGS=-Zmiri-disable-isolation cargo +nightly miri test --lib deferred_system`.
+    #[test]
+    fn handle_outlives_torn_down_root() {
+        let mut app = DioxusTestApp::new(StoresHandle);
+        app.world_mut().init_resource::<Runs>();
+        let call_handle = || (HANDLE.lock().unwrap().as_ref().unwrap())();
+
+        call_handle();
+        app.update();
+        assert_eq!(app.world().resource::<Runs>().0, 1);
+
+        // Scheduled in the same frame as the teardown, so it still runs once
+        call_handle();
+        let root_entity = app.root_entity();
+        app.world_mut().entity_mut(root_entity).despawn_recursive();
+        app.update_frames(2);
+        assert_eq!(app.world().resource::<Runs>().0, 2);
+        assert_eq!(Arc::strong_count(SYSTEM_TOKEN.get().unwrap()), 1);
+
+        call_handle();
+        app.update_frames(2);
+        assert_eq!(app.world().resource::<Runs>().0, 2);
+    }
 }
@@ -1,40 +1,167 @@
-use bevy::{ecs::{
-    entity::Entity,
-    event::{Events, ManualEventReader},
-    system::Resource,
-}, math::Vec2};
-use bevy_mod_picking::events::{Click, Pointer};
+use crate::DioxusUiSurface;
+use bevy::{
+    ecs::{
+        entity::Entity,
+        event::{Events, ManualEventReader},
+        system::Resource,
+        world::World,
+    },
+    hierarchy::Children,
+    input::{
+        keyboard::{KeyCode, KeyboardInput},
+        ButtonState,
+    },
+    math::{Rect, Vec2},
+    transform::components::GlobalTransform,
+    ui::Node,
+    utils::EntityHashMap,
+};
+use bevy_mod_picking::{
+    backend::HitData,
+    events::{Click, Out, Over, Pointer},
+};
 use std::{any::Any, rc::Rc};
 
 // TODO: Other events
 
 // TODO: Naming is still undecided
 #[derive(Default)]
-pub struct PointerInput  {
-    /// Where is the pointer?
+pub struct PointerInput {
+    /// Where is the pointer? Only set for events on a DioxusUiSurface, in the same logical pixels
+    /// as the root's bevy_ui layout.
     pub position: Vec2,
 }
 
+pub struct KeyInput {
+    pub key_code: Option<KeyCode>,
+}
+
 #[derive(Resource, Default)]
 pub struct EventReaders {
     clicks: ManualEventReader<Pointer<Click>>,
+    overs: ManualEventReader<Pointer<Over>>,
+    outs: ManualEventReader<Pointer<Out>>,
+    keys: ManualEventReader<KeyboardInput>,
+    /// There's no focus yet, so keyboard events go to the hovered node and bubble up from there.
+    hovered: Option<Entity>,
+    /// The node that got the mouseover when the pointer entered each DioxusUiSurface mesh, so the
+    /// mouseout goes to the same node.
+    surface_hovers: EntityHashMap<Entity, Entity>,
 }
 
 impl EventReaders {
-    pub fn get_dioxus_events(
-        &mut self,
-        clicks: &Events<Pointer<Click>>,
-    ) -> Vec<(Entity, &'static str, Rc<dyn Any>)> {
+    pub fn get_dioxus_events(&mut self, world: &World) -> Vec<(Entity, &'static str, Rc<dyn Any>)> {
         let mut events: Vec<(Entity, &'static str, Rc<dyn Any>)> = Vec::new();
 
-        for event in self.clicks.read(clicks) {
-            events.push((event.target, "click", Rc::new(PointerInput::default())));
+        for event in self.clicks.read(world.resource::<Events<Pointer<Click>>>()) {
+            if let Some((target, pointer_input)) =
+                pointer_target(event.target, &event.event.hit, world)
+            {
+                events.push((target, "click", Rc::new(pointer_input)));
+            }
+        }
+
+        for event in self.outs.read(world.resource::<Events<Pointer<Out>>>()) {
+            let target = match world.get::<DioxusUiSurface>(event.target) {
+                Some(_) => match self.surface_hovers.remove(&event.target) {
+                    Some(target) => target,
+                    None => continue,
+                },
+                None => event.target,
+            };
+            if self.hovered == Some(target) {
+                self.hovered = None;
+            }
+            events.push((target, "mouseout", Rc::new(PointerInput::default())));
+        }
+        for event in self.overs.read(world.resource::<Events<Pointer<Over>>>()) {
+            let Some((target, pointer_input)) =
+                pointer_target(event.target, &event.event.hit, world)
+            else {
+                continue;
+            };
+            if world.get::<DioxusUiSurface>(event.target).is_some() {
+                self.surface_hovers.insert(event.target, target);
+            }
+            self.hovered = Some(target);
+            events.push((target, "mouseover", Rc::new(pointer_input)));
+        }
+
+        for event in self.keys.read(world.resource::<Events<KeyboardInput>>()) {
+            let Some(hovered) = self.hovered else {
+                continue;
+            };
+            let name = match event.state {
+                ButtonState::Pressed => "keydown",
+                ButtonState::Released => "keyup",
+            };
+            events.push((
+                hovered,
+                name,
+                Rc::new(KeyInput {
+                    key_code: event.key_code,
+                }),
+            ));
         }
 
         events
     }
 }
 
+/// Pointer events on a DioxusUiSurface's mesh go to the topmost node of its root under the hit.
+/// Hits that miss the surface's quad are dropped.
+fn pointer_target(target: Entity, hit: &HitData, world: &World) -> Option<(Entity, PointerInput)> {
+    let Some(surface) = world.get::<DioxusUiSurface>(target) else {
+        return Some((target, PointerInput::default()));
+    };
+
+    let local_position = world
+        .get::<GlobalTransform>(target)?
+        .affine()
+        .inverse()
+        .transform_point3(hit.position?);
+    let uv = Vec2::new(
+        local_position.x / surface.size.x + 0.5,
+        0.5 - local_position.y / surface.size.y,
+    );
+    if !Rect::new(0.0, 0.0, 1.0, 1.0).contains(uv) {
+        return None;
+    }
+
+    let root_rect = node_rect(surface.root, world)?;
+    let position = root_rect.min + uv * root_rect.size();
+    Some((
+        node_at(surface.root, position, world),
+        PointerInput { position },
+    ))
+}
+
+/// Picks the node with the highest stack index under `position`, like bevy_ui's focus system.
+fn node_at(root: Entity, position: Vec2, world: &World) -> Entity {
+    let mut topmost = (0, root);
+    let mut stack = vec![root];
+    while let Some(entity) = stack.pop() {
+        if node_rect(entity, world).is_some_and(|rect| rect.contains(position)) {
+            let stack_index = world.get::<Node>(entity).unwrap().stack_index();
+            if stack_index >= topmost.0 {
+                topmost = (stack_index, entity);
+            }
+        }
+        if let Some(children) = world.get::<Children>(entity) {
+            stack.extend(children.iter());
+        }
+    }
+    topmost.1
+}
+
+fn node_rect(entity: Entity, world: &World) -> Option<Rect> {
+    let node = world.get::<Node>(entity)?;
+    Some(node.logical_rect(world.get::<GlobalTransform>(entity)?))
+}
+
 pub fn is_supported_event(event: &str) -> bool {
-    event == "click"
+    matches!(
+        event,
+        "click" | "mouseover" | "mouseout" | "keydown" | "keyup"
+    )
 }
@@ -0,0 +1,108 @@
+use bevy::utils::{HashMap, HashSet};
+use std::{
+    any::{Any, TypeId},
+    cell::{Ref, RefCell},
+    rc::Rc,
+};
+
+/// Global signal values shared by every DioxusUiRoot, keyed by their type.
+#[derive(Default)]
+pub(crate) struct GlobalSignals {
+    values: RefCell<HashMap<TypeId, Rc<dyn Any>>>,
+    changed: Rc<RefCell<HashSet<TypeId>>>,
+}
+
+impl GlobalSignals {
+    pub fn get_or_default<T: Default + 'static>(&self) -> GlobalSignal<T> {
+        let value = Rc::clone(
+            self.values
+                .borrow_mut()
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Rc::new(RefCell::new(T::default()))),
+        );
+
+        GlobalSignal {
+            value: value.downcast().unwrap(),
+            changed: Rc::clone(&self.changed),
+        }
+    }
+
+    /// Returns the signals written to since the last call.
+    pub fn take_changed(&self) -> HashSet<TypeId> {
+        self.changed.take()
+    }
+}
+
+/// A value shared between all DioxusUiRoots. Writing to it rerenders its subscribers in every root
+/// on the next `DioxusUiSet::Render`.
+pub struct GlobalSignal<T: 'static> {
+    value: Rc<RefCell<T>>,
+    changed: Rc<RefCell<HashSet<TypeId>>>,
+}
+
+impl<T: 'static> Clone for GlobalSignal<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: Rc::clone(&self.value),
+            changed: Rc::clone(&self.changed),
+        }
+    }
+}
+
+impl<T: 'static> GlobalSignal<T> {
+    pub fn read(&self) -> Ref<T> {
+        self.value.borrow()
+    }
+
+    pub fn set(&self, value: T) {
+        self.with_mut(|current| *current = value);
+    }
+
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.changed.borrow_mut().insert(TypeId::of::<T>());
+        f(&mut self.value.borrow_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hooks::use_global_signal, prelude::*, testing::DioxusTestApp, DioxusUiRoot};
+    use dioxus::prelude::*;
+
+    #[derive(Default)]
+    struct Clicks(u32);
+
+    #[test]
+    fn writes_rerender_subscribers_in_every_root() {
+        fn Writer(cx: Scope) -> Element {
+            let clicks = use_global_signal::<Clicks>(cx);
+            let count = clicks.read().0;
+            render! {
+                node {
+                    data_testid: "increment",
+                    onclick: move |_| clicks.with_mut(|clicks| clicks.0 += 1),
+                    "Writer: {count}"
+                }
+            }
+        }
+
+        fn Reader(cx: Scope) -> Element {
+            let count = use_global_signal::<Clicks>(cx).read().0;
+            render! { node { "Reader: {count}" } }
+        }
+
+        let mut app = DioxusTestApp::new(Writer);
+        let increment = app.find_by_test_id("increment").unwrap();
+        let writer_text = app.find_by_text("Writer: 0").unwrap();
+        app.spawn_root(DioxusUiRoot::new(Reader));
+        assert!(app.find_by_text("Reader: 0").is_some());
+
+        app.click(increment);
+        assert_eq!(app.text(writer_text).unwrap(), "Writer: 1");
+        assert!(app.find_by_text("Reader: 1").is_some());
+
+        app.click(increment);
+        assert_eq!(app.text(writer_text).unwrap(), "Writer: 2");
+        assert!(app.find_by_text("Reader: 2").is_some());
+    }
+}
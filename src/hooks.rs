@@ -1,7 +1,15 @@
-use crate::{deferred_system::new_deferred_system, UiContext};
+use crate::{
+    async_world::{AsyncWorld, AsyncWorldQueue},
+    compute_task::{ComputeTask, TaskState},
+    deferred_commands::{DeferredCommandQueue, DioxusUiCommands},
+    deferred_system::{new_deferred_system, DeferredSystemRegistry},
+    global_signal::GlobalSignal,
+    UiContext,
+};
 use bevy::{
     ecs::{
         component::ComponentId,
+        entity::Entity,
         query::{QueryState, ReadOnlyWorldQuery},
         system::{IntoSystem, Query, Resource},
         world::{unsafe_world_cell::UnsafeWorldCell, World},
@@ -12,66 +20,199 @@ use dioxus::{
     core::{ScopeId, ScopeState},
     hooks::use_on_destroy,
 };
+use std::{
+    any::TypeId,
+    cell::{Cell, RefCell},
+    future::Future,
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    rc::Rc,
+};
 
-#[derive(Default)]
+/// Scopes of a single UiRoot that need to rerender when the ECS changes.
+#[derive(Default, Clone)]
 pub(crate) struct EcsSubscriptions {
-    pub resources: Box<HashMap<ComponentId, HashSet<ScopeId>>>,
-    pub world_and_queries: Box<HashSet<ScopeId>>,
+    pub resources: Rc<RefCell<HashMap<ComponentId, HashSet<ScopeId>>>>,
+    pub world_and_queries: Rc<RefCell<HashSet<ScopeId>>>,
+    pub global_signals: Rc<RefCell<HashMap<TypeId, HashSet<ScopeId>>>>,
+}
+
+impl EcsSubscriptions {
+    fn get(cx: &ScopeState) -> Self {
+        cx.consume_context::<EcsSubscriptions>()
+            .expect("Must be used from a dioxus component within a DioxusUiRoot bevy component")
+    }
 }
 
-#[derive(Clone)]
+/// Provided to the VirtualDom of every UiRoot. Components get read-only access to the World while
+/// they render and while their listeners handle events, and any writes go through deferred
+/// systems, commands or AsyncWorld.
+#[derive(Clone, Default)]
 pub(crate) struct EcsContext {
-    pub world: *mut World,
+    world: Rc<Cell<Option<UnsafeWorldCell<'static>>>>,
 }
 
 impl EcsContext {
-    fn get_world(cx: &ScopeState) -> &mut World {
-        unsafe {
-            &mut *cx
-                .consume_context::<EcsContext>()
-                .expect("Must be used from a dioxus component within a DioxusUiRoot bevy component")
-                .world
+    fn get(cx: &ScopeState) -> Self {
+        cx.consume_context::<EcsContext>()
+            .expect("Must be used from a dioxus component within a DioxusUiRoot bevy component")
+    }
+
+    /// Gives components read-only access to `world` while `f` runs, which renders them or
+    /// dispatches events to them.
+    pub fn with_world<R>(&self, world: &World, f: impl FnOnce() -> R) -> R {
+        /// Takes the World back even if `f` panics.
+        struct TakeBack<'a>(&'a Cell<Option<UnsafeWorldCell<'static>>>);
+
+        impl Drop for TakeBack<'_> {
+            fn drop(&mut self) {
+                self.0.set(None);
+            }
         }
+
+        let world = world.as_unsafe_world_cell_readonly();
+        // SAFETY: The cell is only Some until `f` returns or unwinds, and `world` is borrowed
+        // immutably for that whole time. See `world` for how the cell is read.
+        let world =
+            unsafe { mem::transmute::<UnsafeWorldCell<'_>, UnsafeWorldCell<'static>>(world) };
+        self.world.set(Some(world));
+        let _take_back = TakeBack(&self.world);
+        f()
     }
+
+    /// Returns the World lent by `with_world`, panicking outside of it.
+    ///
+    /// The borrow must not be kept once the current render or event dispatch is over. Hooks only
+    /// use it while they run, and DioxusUiWorld checks the cell again on every access.
+    fn world(&self) -> &World {
+        let world = self.world.get().expect(
+            "The World can only be accessed while dioxus components render or handle events",
+        );
+        // SAFETY: The cell is read-only, and the World isn't borrowed mutably while it's lent
+        unsafe { world.world() }
+    }
+
+    fn get_world(cx: &ScopeState) -> DioxusUiWorld {
+        DioxusUiWorld {
+            ecs_context: Self::get(cx),
+            _scope: PhantomData,
+        }
+    }
+}
+
+/// Read-only access to the World from a dioxus component, see `use_world`.
+///
+/// Every access checks that the World is lent to dioxus, which is only the case while components
+/// render and while their listeners handle events. A listener or future that holds on to it and
+/// accesses it at any other time panics instead of reading a World that has since changed. Borrows
+/// of the World are borrows of the DioxusUiWorld, which has the lifetime of the component's scope
+/// and so can't be kept in hook state across renders.
+pub struct DioxusUiWorld<'a> {
+    ecs_context: EcsContext,
+    _scope: PhantomData<&'a ScopeState>,
 }
 
-pub fn use_world<'a>(cx: &'a ScopeState) -> &'a World {
+impl Deref for DioxusUiWorld<'_> {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        self.ecs_context.world()
+    }
+}
+
+/// Read-only access to a resource from a dioxus component, see `use_resource`. Like DioxusUiWorld,
+/// every access checks that the World is lent to dioxus.
+pub struct DioxusUiRes<'a, T: Resource> {
+    world: DioxusUiWorld<'a>,
+    _resource: PhantomData<fn() -> T>,
+}
+
+impl<T: Resource> Deref for DioxusUiRes<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.world.resource()
+    }
+}
+
+/// Queries created while rendering, to be registered once the World can be mutated again. Creating
+/// a QueryState registers the query's components, so it can't happen during a render.
+pub(crate) type QueryRegistrations = Rc<RefCell<Vec<Box<dyn FnOnce(&mut World)>>>>;
+
+/// Registers the queries created by the last render, returning false if there were none.
+pub(crate) fn register_queries(world: &mut World) -> bool {
+    let query_registrations = mem::take(
+        &mut *world
+            .non_send_resource::<UiContext>()
+            .query_registrations
+            .borrow_mut(),
+    );
+    let registered_queries = !query_registrations.is_empty();
+    for register_query in query_registrations {
+        register_query(world);
+    }
+    registered_queries
+}
+
+/// The state of a `use_query` hook. Until the query is registered, it runs against an empty World.
+enum UseQueryState<Q: ReadOnlyWorldQuery, F: ReadOnlyWorldQuery> {
+    Unregistered {
+        empty_world: World,
+        empty_query_state: QueryState<Q, F>,
+        query_state: Rc<RefCell<Option<QueryState<Q, F>>>>,
+    },
+    Registered(QueryState<Q, F>),
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RootEntity(pub Entity);
+
+/// Returns the entity of the DioxusUiRoot this component is rendered in.
+pub fn use_root_entity(cx: &ScopeState) -> Entity {
+    *cx.use_hook(|| {
+        cx.consume_context::<RootEntity>()
+            .expect("Must be used from a dioxus component within a DioxusUiRoot bevy component")
+            .0
+    })
+}
+
+pub fn use_world(cx: &ScopeState) -> DioxusUiWorld {
     let world = EcsContext::get_world(cx);
 
     let scope_id = cx.scope_id();
-    let subscription_manager = *cx.use_hook(|| {
-        let subscription_manager = &mut world
-            .non_send_resource_mut::<UiContext>()
-            .subscriptions
-            .world_and_queries;
-        subscription_manager.insert(scope_id);
-        Box::as_mut(subscription_manager) as *mut HashSet<ScopeId>
-    });
+    let subscription_manager = cx
+        .use_hook(|| {
+            let subscription_manager = EcsSubscriptions::get(cx).world_and_queries;
+            subscription_manager.borrow_mut().insert(scope_id);
+            subscription_manager
+        })
+        .clone();
     use_on_destroy(cx, move || {
-        unsafe { &mut *subscription_manager }.remove(&scope_id);
+        subscription_manager.borrow_mut().remove(&scope_id);
     });
 
     world
 }
 
-pub fn use_resource<'a, T: Resource>(cx: &'a ScopeState) -> &'a T {
+pub fn use_resource<T: Resource>(cx: &ScopeState) -> DioxusUiRes<T> {
     let world = EcsContext::get_world(cx);
 
     let resource_id = world.components().resource_id::<T>().unwrap();
     let scope_id = cx.scope_id();
-    let subscription_manager = *cx.use_hook(|| {
-        let subscription_manager = &mut world
-            .non_send_resource_mut::<UiContext>()
-            .subscriptions
-            .resources;
-        subscription_manager
-            .entry(resource_id)
-            .or_default()
-            .insert(scope_id);
-        Box::as_mut(subscription_manager) as *mut HashMap<ComponentId, HashSet<ScopeId>>
-    });
+    let subscription_manager = cx
+        .use_hook(|| {
+            let subscription_manager = EcsSubscriptions::get(cx).resources;
+            subscription_manager
+                .borrow_mut()
+                .entry(resource_id)
+                .or_default()
+                .insert(scope_id);
+            subscription_manager
+        })
+        .clone();
     use_on_destroy(cx, move || {
-        let subscription_manager = &mut unsafe { &mut *subscription_manager };
+        let mut subscription_manager = subscription_manager.borrow_mut();
         let resource_subscriptions = subscription_manager.get_mut(&resource_id).unwrap();
         resource_subscriptions.remove(&scope_id);
         if resource_subscriptions.is_empty() {
@@ -79,70 +220,244 @@ pub fn use_resource<'a, T: Resource>(cx: &'a ScopeState) -> &'a T {
         }
     });
 
-    world.resource()
+    DioxusUiRes {
+        world,
+        _resource: PhantomData,
+    }
+}
+
+/// Returns a signal shared by every DioxusUiRoot, initialized with `T::default()`.
+pub fn use_global_signal<T: Default + 'static>(cx: &ScopeState) -> GlobalSignal<T> {
+    let scope_id = cx.scope_id();
+    let (global_signal, subscription_manager) = cx.use_hook(|| {
+        let global_signal = EcsContext::get_world(cx)
+            .non_send_resource::<UiContext>()
+            .global_signals
+            .get_or_default::<T>();
+
+        let subscription_manager = EcsSubscriptions::get(cx).global_signals;
+        subscription_manager
+            .borrow_mut()
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .insert(scope_id);
+
+        (global_signal, subscription_manager)
+    });
+    let subscription_manager = subscription_manager.clone();
+    use_on_destroy(cx, move || {
+        let mut subscription_manager = subscription_manager.borrow_mut();
+        let signal_subscriptions = subscription_manager.get_mut(&TypeId::of::<T>()).unwrap();
+        signal_subscriptions.remove(&scope_id);
+        if signal_subscriptions.is_empty() {
+            subscription_manager.remove(&TypeId::of::<T>());
+        }
+    });
+
+    global_signal.clone()
 }
 
+/// Queries the World, rerendering the component every frame.
+///
+/// The query matches nothing during the component's first render, as creating it registers its
+/// components in the World. It's registered right after that render, and the component rerenders
+/// with the query's results before the frame's mutations are done.
 pub fn use_query<'a, Q>(cx: &'a ScopeState) -> DioxusUiQuery<'a, Q, ()>
 where
-    Q: ReadOnlyWorldQuery,
+    Q: ReadOnlyWorldQuery + 'static,
 {
     use_query_filtered(cx)
 }
 
+/// Like `use_query`, with a query filter.
 pub fn use_query_filtered<'a, Q, F>(cx: &'a ScopeState) -> DioxusUiQuery<'a, Q, F>
 where
-    Q: ReadOnlyWorldQuery,
-    F: ReadOnlyWorldQuery,
+    Q: ReadOnlyWorldQuery + 'static,
+    F: ReadOnlyWorldQuery + 'static,
 {
     let world = EcsContext::get_world(cx);
 
     let scope_id = cx.scope_id();
-    let subscription_manager = *cx.use_hook(|| {
-        let subscription_manager = &mut world
-            .non_send_resource_mut::<UiContext>()
-            .subscriptions
-            .world_and_queries;
-        subscription_manager.insert(scope_id);
-        Box::as_mut(subscription_manager) as *mut HashSet<ScopeId>
-    });
+    let subscription_manager = cx
+        .use_hook(|| {
+            let subscription_manager = EcsSubscriptions::get(cx).world_and_queries;
+            subscription_manager.borrow_mut().insert(scope_id);
+            subscription_manager
+        })
+        .clone();
     use_on_destroy(cx, move || {
-        unsafe { &mut *subscription_manager }.remove(&scope_id);
+        subscription_manager.borrow_mut().remove(&scope_id);
+    });
+
+    let use_query_state = cx.use_hook(|| {
+        let query_state = Rc::new(RefCell::new(None));
+        let registered_query_state = Rc::downgrade(&query_state);
+        let schedule_update = cx.schedule_update();
+        world
+            .non_send_resource::<UiContext>()
+            .query_registrations
+            .borrow_mut()
+            .push(Box::new(move |world: &mut World| {
+                // The component was destroyed before its query could be registered
+                let Some(registered_query_state) = registered_query_state.upgrade() else {
+                    return;
+                };
+                *registered_query_state.borrow_mut() = Some(QueryState::new(world));
+                schedule_update();
+            }));
+
+        let mut empty_world = World::new();
+        let empty_query_state = QueryState::new(&mut empty_world);
+        UseQueryState::Unregistered {
+            empty_world,
+            empty_query_state,
+            query_state,
+        }
     });
 
-    DioxusUiQuery {
-        query_state: QueryState::new(world),
-        world_cell: world.as_unsafe_world_cell(),
+    let registered_query_state = match use_query_state {
+        UseQueryState::Unregistered { query_state, .. } => query_state.borrow_mut().take(),
+        UseQueryState::Registered(_) => None,
+    };
+    if let Some(query_state) = registered_query_state {
+        *use_query_state = UseQueryState::Registered(query_state);
+    }
+
+    match use_query_state {
+        UseQueryState::Unregistered {
+            empty_world,
+            empty_query_state,
+            ..
+        } => DioxusUiQuery {
+            query_state: empty_query_state,
+            world: QueryWorld::Empty(empty_world),
+        },
+        UseQueryState::Registered(query_state) => {
+            query_state.update_archetypes(&world);
+            DioxusUiQuery {
+                query_state,
+                world: QueryWorld::Lent(world),
+            }
+        }
     }
 }
 
-pub fn use_system<S, M>(cx: &ScopeState, system: S) -> impl Fn() + Send + Sync + Copy
+pub fn use_system<'a, S, M>(
+    cx: &'a ScopeState,
+    system: S,
+) -> &'a (impl Fn() + Send + Sync + Clone + 'static)
 where
     S: IntoSystem<(), (), M> + 'static,
     M: 'static,
 {
-    cx.use_hook(|| new_deferred_system(system, EcsContext::get_world(cx)))
-        .0
+    let (deferred_system, unregister) = cx.use_hook(|| {
+        new_deferred_system(
+            system,
+            EcsContext::get_world(cx).resource::<DeferredSystemRegistry>(),
+        )
+    });
+    use_on_destroy(cx, unregister.clone());
+
+    deferred_system
 }
 
+pub fn use_commands(cx: &ScopeState) -> DioxusUiCommands {
+    cx.use_hook(|| {
+        EcsContext::get_world(cx)
+            .resource::<DeferredCommandQueue>()
+            .handle()
+    })
+    .clone()
+}
+
+pub fn use_async_world(cx: &ScopeState) -> AsyncWorld {
+    cx.use_hook(|| {
+        EcsContext::get_world(cx)
+            .resource::<AsyncWorldQueue>()
+            .handle()
+    })
+    .clone()
+}
+
+/// Runs a future on bevy's `AsyncComputeTaskPool` and rerenders the component once it finishes.
+///
+/// The task is cancelled when the component is destroyed.
+pub fn use_task<'a, T, E, Fut>(
+    cx: &'a ScopeState,
+    future: impl FnOnce() -> Fut,
+) -> &'a TaskState<T, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+{
+    let (compute_task, task_state) = cx.use_hook(|| {
+        let compute_task = ComputeTask::spawn(
+            future(),
+            cx.schedule_update(),
+            &EcsContext::get_world(cx)
+                .non_send_resource::<UiContext>()
+                .compute_tasks,
+        );
+        (compute_task, TaskState::Pending)
+    });
+
+    if let Some(output) = compute_task.borrow_mut().take_output() {
+        *task_state = match output {
+            Ok(value) => TaskState::Ready(value),
+            Err(error) => TaskState::Error(error),
+        };
+    }
+
+    task_state
+}
+
+/// The result of `use_query`. Like DioxusUiWorld, every call to `query` checks that the World is
+/// lent to dioxus.
 pub struct DioxusUiQuery<'a, Q: ReadOnlyWorldQuery, F: ReadOnlyWorldQuery> {
-    query_state: QueryState<Q, F>,
-    world_cell: UnsafeWorldCell<'a>,
+    query_state: &'a QueryState<Q, F>,
+    world: QueryWorld<'a>,
+}
+
+enum QueryWorld<'a> {
+    Lent(DioxusUiWorld<'a>),
+    /// The hook's own empty World, until the query is registered.
+    Empty(&'a World),
 }
 
-impl<'a, Q, F> DioxusUiQuery<'a, Q, F>
+impl<Q, F> DioxusUiQuery<'_, Q, F>
 where
     Q: ReadOnlyWorldQuery,
     F: ReadOnlyWorldQuery,
 {
     pub fn query(&self) -> Query<Q, F> {
-        unsafe {
-            Query::new(
-                self.world_cell,
-                &self.query_state,
-                self.world_cell.last_change_tick(),
-                self.world_cell.change_tick(),
-                true,
-            )
-        }
+        let world = match &self.world {
+            QueryWorld::Lent(world) => &**world,
+            QueryWorld::Empty(world) => *world,
+        };
+        read_only_query(self.query_state, world)
+    }
+}
+
+/// Runs a read-only query, without the `&mut World` that `QueryState::iter` takes to update the
+/// query's archetypes.
+pub(crate) fn read_only_query<'w, 's, Q, F>(
+    query_state: &'s QueryState<Q, F>,
+    world: &'w World,
+) -> Query<'w, 's, Q, F>
+where
+    Q: ReadOnlyWorldQuery,
+    F: ReadOnlyWorldQuery,
+{
+    let world_cell = world.as_unsafe_world_cell_readonly();
+    // SAFETY: The query is read-only and the World is borrowed immutably for `'w`
+    unsafe {
+        Query::new(
+            world_cell,
+            query_state,
+            world_cell.last_change_tick(),
+            world_cell.change_tick(),
+            true,
+        )
     }
 }
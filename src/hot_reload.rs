@@ -0,0 +1,49 @@
+use bevy::ecs::system::Resource;
+use dioxus::{
+    core::Template,
+    hot_reload::{connect, HotReloadMsg},
+};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+/// Updated rsx templates, applied to every UiRoot before the next render.
+///
+/// `DioxusUiPlugin::with_hot_reload` feeds this from the dioxus hot-reload server, and anything
+/// else, like a custom file watcher or a test, can push updates through `sender`.
+#[derive(Resource)]
+pub struct DioxusUiTemplateUpdates {
+    sender: Sender<Template<'static>>,
+    receiver: Mutex<Receiver<Template<'static>>>,
+}
+
+impl Default for DioxusUiTemplateUpdates {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+impl DioxusUiTemplateUpdates {
+    pub fn sender(&self) -> Sender<Template<'static>> {
+        self.sender.clone()
+    }
+
+    pub(crate) fn take(&self) -> Vec<Template<'static>> {
+        self.receiver.lock().unwrap().try_iter().collect()
+    }
+}
+
+/// Forwards template updates from the server started by `dioxus::prelude::hot_reload_init!`.
+pub fn connect_to_hot_reload_server(sender: Sender<Template<'static>>) {
+    connect(move |message| {
+        if let HotReloadMsg::UpdateTemplate(template) = message {
+            // The app was closed if the receiver is gone
+            let _ = sender.send(template);
+        }
+    });
+}
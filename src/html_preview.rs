@@ -0,0 +1,375 @@
+use crate::{
+    apply_mutations::{CLASS_ATTRIBUTE, TEST_ID_ATTRIBUTE},
+    DioxusStylesheet, Theme,
+};
+use bevy::utils::HashMap;
+use dioxus::core::{
+    BorrowedAttributeValue, Element, ElementId, Mutation, Mutations, Scope, Template,
+    TemplateAttribute, TemplateNode, VirtualDom,
+};
+use std::fmt::Write;
+
+/// Renders a component to a static HTML page with inline CSS, for previewing layout and colors in
+/// a browser without starting bevy.
+///
+/// Each node's classes and style attributes are resolved into its inline style in the same order
+/// as in bevy_dioxus, see DioxusStylesheet, and `var(--token)` values are taken from `theme`.
+///
+/// Only the first render is captured, and the component can't use hooks that access the ECS.
+pub fn render_to_html(
+    root_component: fn(Scope) -> Element,
+    stylesheet: Option<&DioxusStylesheet>,
+    theme: &Theme,
+) -> String {
+    let mut virtual_dom = VirtualDom::new(root_component);
+    let mut document = HtmlDocument::default();
+    document.apply_mutations(virtual_dom.rebuild());
+    document.to_html(stylesheet, theme)
+}
+
+enum HtmlContent {
+    Element(Vec<(String, String)>),
+    Text(String),
+}
+
+struct HtmlNode {
+    content: HtmlContent,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// The same stack machine as `apply_mutations`, but building HTML nodes instead of bevy entities.
+struct HtmlDocument {
+    nodes: Vec<HtmlNode>,
+    element_id_to_node: HashMap<ElementId, usize>,
+    templates: HashMap<String, Template<'static>>,
+}
+
+impl Default for HtmlDocument {
+    fn default() -> Self {
+        let body = HtmlNode {
+            content: HtmlContent::Element(Vec::new()),
+            parent: None,
+            children: Vec::new(),
+        };
+        Self {
+            nodes: vec![body],
+            element_id_to_node: HashMap::from([(ElementId(0), 0)]),
+            templates: HashMap::new(),
+        }
+    }
+}
+
+impl HtmlDocument {
+    fn apply_mutations(&mut self, mutations: Mutations) {
+        for template in mutations.templates {
+            self.templates.insert(template.name.to_owned(), template);
+        }
+
+        let mut stack = vec![0];
+        for edit in mutations.edits {
+            match edit {
+                Mutation::AppendChildren { id, m } => {
+                    let parent = self.element_id_to_node[&id];
+                    for child in stack.drain((stack.len() - m)..) {
+                        self.insert_child(parent, self.nodes[parent].children.len(), child);
+                    }
+                }
+                Mutation::AssignId { path, id } => {
+                    let node = self.node_at_path(*stack.last().unwrap(), path);
+                    self.element_id_to_node.insert(id, node);
+                }
+                Mutation::CreatePlaceholder { id } => {
+                    let node = self.push_node(HtmlContent::Element(Vec::new()));
+                    self.element_id_to_node.insert(id, node);
+                    stack.push(node);
+                }
+                Mutation::CreateTextNode { value, id } => {
+                    let node = self.push_node(HtmlContent::Text(value.to_owned()));
+                    self.element_id_to_node.insert(id, node);
+                    stack.push(node);
+                }
+                Mutation::HydrateText { path, value, id } => {
+                    let node = self.node_at_path(*stack.last().unwrap(), path);
+                    self.nodes[node].content = HtmlContent::Text(value.to_owned());
+                    self.element_id_to_node.insert(id, node);
+                }
+                Mutation::LoadTemplate { name, index, id } => {
+                    let template = self.templates[name];
+                    let node = self.instantiate(&template.roots[index]);
+                    self.element_id_to_node.insert(id, node);
+                    stack.push(node);
+                }
+                Mutation::ReplaceWith { id, m } => {
+                    let new_nodes = stack.split_off(stack.len() - m);
+                    self.replace(self.element_id_to_node[&id], &new_nodes);
+                }
+                Mutation::ReplacePlaceholder { path, m } => {
+                    let existing = self.node_at_path(stack[stack.len() - m - 1], path);
+                    let new_nodes = stack.split_off(stack.len() - m);
+                    self.replace(existing, &new_nodes);
+                }
+                Mutation::InsertAfter { id, m } => {
+                    let new_nodes = stack.split_off(stack.len() - m);
+                    let (parent, index) = self.position(self.element_id_to_node[&id]);
+                    for (offset, node) in new_nodes.into_iter().enumerate() {
+                        self.insert_child(parent, index + 1 + offset, node);
+                    }
+                }
+                Mutation::InsertBefore { id, m } => {
+                    let new_nodes = stack.split_off(stack.len() - m);
+                    let (parent, index) = self.position(self.element_id_to_node[&id]);
+                    for (offset, node) in new_nodes.into_iter().enumerate() {
+                        self.insert_child(parent, index + offset, node);
+                    }
+                }
+                Mutation::SetAttribute {
+                    name,
+                    value,
+                    id,
+                    ns: _,
+                } => {
+                    let BorrowedAttributeValue::Text(value) = value else {
+                        panic!("Encountered unsupported bevy_dioxus attribute `{name}: {value:?}`.")
+                    };
+                    let node = self.element_id_to_node[&id];
+                    if let HtmlContent::Element(attributes) = &mut self.nodes[node].content {
+                        attributes.retain(|(existing, _)| existing != name);
+                        attributes.push((name.to_owned(), value.to_owned()));
+                    }
+                }
+                Mutation::SetText { value, id } => {
+                    let node = self.element_id_to_node[&id];
+                    self.nodes[node].content = HtmlContent::Text(value.to_owned());
+                }
+                Mutation::NewEventListener { .. } | Mutation::RemoveEventListener { .. } => {}
+                Mutation::Remove { id } => {
+                    let node = self.element_id_to_node[&id];
+                    self.detach(node);
+                }
+                Mutation::PushRoot { id } => stack.push(self.element_id_to_node[&id]),
+            }
+        }
+    }
+
+    fn push_node(&mut self, content: HtmlContent) -> usize {
+        self.nodes.push(HtmlNode {
+            content,
+            parent: None,
+            children: Vec::new(),
+        });
+        self.nodes.len() - 1
+    }
+
+    fn instantiate(&mut self, template_node: &TemplateNode) -> usize {
+        match template_node {
+            TemplateNode::Element {
+                tag,
+                namespace: _,
+                attrs,
+                children,
+            } => {
+                if *tag != "node" {
+                    panic!(
+                        "Encountered unsupported bevy_dioxus tag `{tag}`. Only `node` is supported."
+                    );
+                }
+                let attributes = attrs
+                    .iter()
+                    .filter_map(|attribute| match attribute {
+                        TemplateAttribute::Static {
+                            name,
+                            value,
+                            namespace: _,
+                        } => Some((name.to_string(), value.to_string())),
+                        TemplateAttribute::Dynamic { .. } => None,
+                    })
+                    .collect();
+                let node = self.push_node(HtmlContent::Element(attributes));
+                for child in children.iter() {
+                    let child = self.instantiate(child);
+                    self.insert_child(node, self.nodes[node].children.len(), child);
+                }
+                node
+            }
+            TemplateNode::Text { text } => self.push_node(HtmlContent::Text(text.to_string())),
+            TemplateNode::Dynamic { id: _ } => self.push_node(HtmlContent::Element(Vec::new())),
+            TemplateNode::DynamicText { id: _ } => self.push_node(HtmlContent::Text(String::new())),
+        }
+    }
+
+    fn node_at_path(&self, mut node: usize, path: &[u8]) -> usize {
+        for index in path {
+            node = self.nodes[node].children[*index as usize];
+        }
+        node
+    }
+
+    fn position(&self, node: usize) -> (usize, usize) {
+        let parent = self.nodes[node].parent.unwrap();
+        let index = self.nodes[parent]
+            .children
+            .iter()
+            .position(|child| *child == node)
+            .unwrap();
+        (parent, index)
+    }
+
+    fn insert_child(&mut self, parent: usize, index: usize, child: usize) {
+        self.detach(child);
+        self.nodes[parent].children.insert(index, child);
+        self.nodes[child].parent = Some(parent);
+    }
+
+    fn replace(&mut self, existing: usize, new_nodes: &[usize]) {
+        let (parent, index) = self.position(existing);
+        for (offset, node) in new_nodes.iter().enumerate() {
+            self.insert_child(parent, index + offset, *node);
+        }
+        self.detach(existing);
+    }
+
+    fn detach(&mut self, node: usize) {
+        if let Some(parent) = self.nodes[node].parent.take() {
+            self.nodes[parent].children.retain(|child| *child != node);
+        }
+    }
+
+    fn to_html(&self, stylesheet: Option<&DioxusStylesheet>, theme: &Theme) -> String {
+        let mut html =
+            String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n</head>\n");
+
+        html.push_str(
+            "<body style=\"margin: 0; width: 100vw; height: 100vh; display: flex; background-color: #000000; color: #ffffff; font-size: 12px;\">\n",
+        );
+        for child in &self.nodes[0].children {
+            self.write_node(*child, 1, stylesheet, theme, &mut html);
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn write_node(
+        &self,
+        node: usize,
+        depth: usize,
+        stylesheet: Option<&DioxusStylesheet>,
+        theme: &Theme,
+        html: &mut String,
+    ) {
+        let indent = "  ".repeat(depth);
+        match &self.nodes[node].content {
+            HtmlContent::Text(text) => {
+                writeln!(html, "{indent}<span>{}</span>", escape(text)).unwrap();
+            }
+            HtmlContent::Element(attributes) => {
+                let mut html_attributes = String::new();
+                for (name, value) in attributes {
+                    if name == TEST_ID_ATTRIBUTE || name == CLASS_ATTRIBUTE {
+                        write!(html_attributes, " {name}=\"{}\"", escape(value)).unwrap();
+                    }
+                }
+                // bevy_ui nodes are flexbox containers with border-box sizing and solid borders
+                let mut style = String::from(
+                    "display: flex; position: relative; box-sizing: border-box; border: 0 solid;",
+                );
+                for (name, value) in style_declarations(attributes, stylesheet, theme) {
+                    write!(style, " {name}: {};", css_value(value)).unwrap();
+                }
+
+                write!(html, "{indent}<div{html_attributes}").unwrap();
+                writeln!(html, " style=\"{}\">", escape(&style)).unwrap();
+                for child in &self.nodes[node].children {
+                    self.write_node(*child, depth + 1, stylesheet, theme, html);
+                }
+                writeln!(html, "{indent}</div>").unwrap();
+            }
+        }
+    }
+}
+
+/// A node's class declarations in order, then its own style attributes, with later declarations
+/// replacing earlier ones of the same attribute, like StyleAttributes::resolve.
+fn style_declarations<'a>(
+    attributes: &'a [(String, String)],
+    stylesheet: Option<&'a DioxusStylesheet>,
+    theme: &'a Theme,
+) -> Vec<(&'a str, &'a str)> {
+    let class = attributes
+        .iter()
+        .find(|(name, _)| name == CLASS_ATTRIBUTE)
+        .map_or("", |(_, class)| class.as_str());
+    let class_declarations = class
+        .split_whitespace()
+        .filter_map(|class| stylesheet?.get_class(class))
+        .flatten();
+    let inline = attributes
+        .iter()
+        .filter(|(name, _)| name != TEST_ID_ATTRIBUTE && name != CLASS_ATTRIBUTE);
+
+    let mut declarations: Vec<(&str, &str)> = Vec::new();
+    for (name, value) in class_declarations.chain(inline) {
+        let (name, value) = (name.as_str(), value.as_str());
+        let value = theme.resolve(value).unwrap_or(value);
+        match declarations
+            .iter_mut()
+            .find(|(existing_name, _)| *existing_name == name)
+        {
+            Some((_, existing_value)) => *existing_value = value,
+            None => declarations.push((name, value)),
+        }
+    }
+    declarations
+}
+
+/// Unitless numbers are pixels in bevy_dioxus, like in `parse_px`.
+fn css_value(value: &str) -> String {
+    if value.parse::<f32>().is_ok() {
+        format!("{value}px")
+    } else {
+        value.to_owned()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, render_to_html, DioxusStylesheet, Theme};
+    use dioxus::prelude::*;
+
+    #[test]
+    fn resolves_classes_inline_in_attribute_order() {
+        fn Card(cx: Scope) -> Element {
+            render! {
+                node { class: "card wide", color: "#ffffff", "Card" }
+            }
+        }
+
+        fn Accent(cx: Scope) -> Element {
+            render! {
+                node { class: "card", "Accent" }
+            }
+        }
+
+        let stylesheet = DioxusStylesheet::default()
+            .with_class("card", [("padding", "8"), ("color", "var(--accent)")])
+            .with_class("wide", [("padding", "16"), ("width", "100%")]);
+        let theme = Theme::empty().with_token("accent", "#ff0000");
+        let html = render_to_html(Card, Some(&stylesheet), &theme);
+        assert!(html.contains(
+            "<div class=\"card wide\" style=\"display: flex; position: relative; \
+             box-sizing: border-box; border: 0 solid; padding: 16px; color: #ffffff; width: 100%;\">"
+        ));
+
+        // Without the inline color, the class's theme token is resolved against the given theme
+        let html = render_to_html(Accent, Some(&stylesheet), &theme);
+        assert!(html.contains("padding: 8px; color: #ff0000;"));
+        assert!(!render_to_html(Accent, None, &theme).contains("padding"));
+    }
+}
@@ -3,71 +3,384 @@
 #![allow(non_camel_case_types)]
 
 mod apply_mutations;
-pub mod colors;
+mod async_world;
+mod color;
+mod compute_task;
+mod css;
+mod deferred_commands;
 mod deferred_system;
 mod events;
+mod global_signal;
 pub mod hooks;
+mod hot_reload;
+mod html_preview;
+mod root_component;
+#[cfg(any(test, feature = "testing"))]
+mod snapshot;
+mod stylesheet;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod text_style;
+mod theme;
 mod tick;
 
 use self::{
-    apply_mutations::BevyTemplate, deferred_system::DeferredSystemRegistry, events::EventReaders,
-    hooks::EcsSubscriptions, tick::tick_dioxus_ui,
+    apply_mutations::{BevyTemplate, TemplateInstance},
+    async_world::AsyncWorldQueue,
+    compute_task::ComputeTasks,
+    css::sync_stylesheet_asset,
+    deferred_commands::DeferredCommandQueue,
+    deferred_system::DeferredSystemRegistry,
+    events::EventReaders,
+    global_signal::GlobalSignals,
+    hooks::{EcsContext, EcsSubscriptions, QueryRegistrations, RootEntity},
+    hot_reload::connect_to_hot_reload_server,
+    root_component::{AnyRootComponent, RootComponent},
+    text_style::inherit_text_styles,
+    tick::{dispatch_ui_events, render_ui, run_deferred_ui_work},
 };
 use bevy::{
     app::{App, Plugin, Update},
-    ecs::{bundle::Bundle, component::Component, entity::Entity},
-    prelude::Deref,
+    asset::AssetApp,
+    ecs::{
+        bundle::Bundle,
+        component::Component,
+        entity::Entity,
+        schedule::{
+            BoxedScheduleLabel, Condition, IntoSystemConfigs, IntoSystemSetConfigs, ScheduleLabel,
+            SystemSet, SystemSetConfigs,
+        },
+        system::Resource,
+        world::World,
+    },
+    math::Vec2,
     ui::node_bundles::NodeBundle,
-    utils::{EntityHashMap, HashMap},
+    utils::{Duration, EntityHashMap, HashMap, Instant},
+};
+use dioxus::core::{
+    Element, ElementId, Properties, Scope, ScopeId, ScopeState, Template, VirtualDom,
+};
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
 };
-use dioxus::core::{Element, ElementId, Scope, VirtualDom};
 
+pub use async_world::{AsyncWorld, WorldAccess};
 pub use bevy_mod_picking;
+pub use compute_task::TaskState;
+pub use css::{CssError, CssLoader, DioxusStylesheetHandle};
+pub use deferred_commands::DioxusUiCommands;
 pub use dioxus;
+pub use events::{KeyInput, PointerInput};
+pub use global_signal::GlobalSignal;
+pub use hot_reload::DioxusUiTemplateUpdates;
+pub use html_preview::render_to_html;
+pub use stylesheet::DioxusStylesheet;
+pub use text_style::InheritedTextStyle;
+pub use theme::Theme;
+
+pub struct DioxusUiPlugin {
+    schedule: BoxedScheduleLabel,
+    run_condition: Option<Box<dyn Fn(SystemSetConfigs) -> SystemSetConfigs + Send + Sync>>,
+    render_budget: Option<Duration>,
+    template_pool_size: usize,
+    hot_reload: bool,
+}
+
+impl Default for DioxusUiPlugin {
+    fn default() -> Self {
+        Self {
+            schedule: Box::new(Update),
+            run_condition: None,
+            render_budget: None,
+            template_pool_size: 256,
+            hot_reload: false,
+        }
+    }
+}
+
+impl DioxusUiPlugin {
+    /// Runs the UI systems in `schedule` instead of `Update`.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = Box::new(schedule);
+        self
+    }
+
+    /// Only runs the UI systems when `condition` is true.
+    pub fn run_if<M: 'static>(
+        mut self,
+        condition: impl Condition<M> + Clone + Send + Sync + 'static,
+    ) -> Self {
+        self.run_condition = Some(Box::new(move |sets| sets.run_if(condition.clone())));
+        self
+    }
+
+    /// Sets the initial DioxusUiRenderBudget.
+    pub fn with_render_budget(mut self, render_budget: Duration) -> Self {
+        self.render_budget = Some(render_budget);
+        self
+    }
+
+    /// Sets the initial DioxusUiTemplatePoolSize.
+    pub fn with_template_pool_size(mut self, template_pool_size: usize) -> Self {
+        self.template_pool_size = template_pool_size;
+        self
+    }
 
-pub struct DioxusUiPlugin;
+    /// Applies rsx template updates from the dioxus hot-reload server to the running UI. Call
+    /// `dioxus::prelude::hot_reload_init!()` at the start of `main` to start the server.
+    pub fn with_hot_reload(mut self) -> Self {
+        self.hot_reload = true;
+        self
+    }
+}
 
 impl Plugin for DioxusUiPlugin {
     fn build(&self, app: &mut App) {
+        let sets = (
+            DioxusUiSet::RunDeferredSystems,
+            DioxusUiSet::DispatchEvents,
+            DioxusUiSet::Render,
+        )
+            .chain();
+        let sets = match &self.run_condition {
+            Some(run_condition) => run_condition(sets),
+            None => sets,
+        };
+
         app.init_non_send_resource::<UiContext>()
             .init_resource::<DeferredSystemRegistry>()
+            .init_resource::<DeferredCommandQueue>()
+            .init_resource::<AsyncWorldQueue>()
             .init_resource::<EventReaders>()
-            .add_systems(Update, tick_dioxus_ui);
+            .init_resource::<DioxusUiTemplateUpdates>()
+            .init_resource::<DioxusStylesheet>()
+            .init_resource::<Theme>()
+            .init_asset::<DioxusStylesheet>()
+            .init_asset_loader::<CssLoader>()
+            .insert_resource(DioxusUiRenderBudget(self.render_budget))
+            .insert_resource(DioxusUiTemplatePoolSize(self.template_pool_size))
+            .configure_sets(self.schedule.clone(), sets)
+            .add_systems(
+                self.schedule.clone(),
+                (
+                    sync_stylesheet_asset
+                        .before(run_deferred_ui_work)
+                        .in_set(DioxusUiSet::RunDeferredSystems),
+                    run_deferred_ui_work.in_set(DioxusUiSet::RunDeferredSystems),
+                    dispatch_ui_events.in_set(DioxusUiSet::DispatchEvents),
+                    render_ui.in_set(DioxusUiSet::Render),
+                    inherit_text_styles
+                        .after(render_ui)
+                        .in_set(DioxusUiSet::Render),
+                ),
+            );
+
+        if self.hot_reload {
+            connect_to_hot_reload_server(app.world.resource::<DioxusUiTemplateUpdates>().sender());
+        }
     }
 }
 
+/// The stages of updating dioxus UI each frame, in the order they run.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DioxusUiSet {
+    /// Runs systems, commands and async World requests queued from dioxus components, and applies
+    /// the stylesheet in DioxusStylesheetHandle.
+    RunDeferredSystems,
+    /// Syncs UiRoots with DioxusUiRoot entities and dispatches picking events to them.
+    DispatchEvents,
+    /// Rerenders dirty components and applies their mutations to the bevy UI.
+    Render,
+}
+
+/// Time each frame after which no more roots start rendering. The budget skips whole roots: a root
+/// that starts rendering renders all of its dirty components, however long that takes, so a single
+/// expensive root can still overrun it. Roots that didn't get to render keep their dirty components
+/// and are rendered first next frame.
+#[derive(Resource, Default, Clone, Copy)]
+pub struct DioxusUiRenderBudget(pub Option<Duration>);
+
+/// Maximum number of removed instances that each UiRoot keeps per template root, hidden, to reuse
+/// for the next instance instead of spawning its entities again. Instances removed from a full pool
+/// are despawned, and a size of zero turns reuse off. Pools don't shrink otherwise, they're only
+/// despawned when their template is hot-reloaded or their UiRoot is torn down.
+#[derive(Resource, Clone, Copy)]
+pub struct DioxusUiTemplatePoolSize(pub usize);
+
+/// Maximum number of renders per second of the DioxusUiRoot on the same entity. A rate of zero or
+/// less doesn't limit renders, like a root without a DioxusUiUpdateRate.
+#[derive(Component, Clone, Copy)]
+pub struct DioxusUiUpdateRate(pub f32);
+
+/// Pauses the DioxusUiRoot on the same entity without losing its state. A root that's hidden, by
+/// its own Visibility or an ancestor's, is paused as well. Changes while paused are rendered at
+/// once when resumed.
+#[derive(Component, Default, Clone, Copy)]
+pub struct DioxusUiPaused;
+
+/// Set from the `data-testid` attribute, so tests can find UI nodes.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct DioxusUiTestId(pub String);
+
+/// The camera that the DioxusUiRoot on the same entity is shown with. `vw` and `vh` resolve against
+/// that camera's window instead of the primary window. Cameras that render to an image use the
+/// primary window as well.
+///
+/// bevy_ui in the pinned bevy version still draws every root with every camera that shows UI, see
+/// `UiCameraConfig`, so this sizes the root for its window but doesn't hide it from other cameras.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DioxusUiTargetCamera(pub Entity);
+
+/// Shows the DioxusUiRoot on the `root` entity on a flat mesh in the world, like a terminal screen
+/// or a sign, through a camera that renders the UI to the mesh's texture. Goes on the mesh entity.
+///
+/// `size` is the size of the mesh's quad in its local XY plane, centered on its origin, with the
+/// top left of the root at the quad's top left corner. Picking events on the mesh are mapped from
+/// the hit on the quad to the root's UI and go to the topmost node there, with the mapped position
+/// in PointerInput. Pointer moves within the mesh don't move the hover to other nodes yet.
+///
+/// bevy_ui in the pinned bevy version still lays the root out against the primary window and draws
+/// it with every camera that shows UI, see DioxusUiTargetCamera.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct DioxusUiSurface {
+    pub root: Entity,
+    pub size: Vec2,
+}
+
+/// A screen-space UI root, laid out and drawn like any other bevy_ui node tree.
 #[derive(Bundle)]
 pub struct DioxusUiBundle {
     pub dioxus_ui_root: DioxusUiRoot,
     pub node_bundle: NodeBundle,
 }
 
-#[derive(Component, Deref, Hash, PartialEq, Eq, Clone, Copy)]
-pub struct DioxusUiRoot(pub fn(Scope) -> Element);
+#[derive(Component, Clone)]
+pub struct DioxusUiRoot(Arc<dyn AnyRootComponent>);
+
+impl DioxusUiRoot {
+    pub fn new(component: fn(Scope) -> Element) -> Self {
+        Self::with_props(component, ())
+    }
+
+    /// Changing the props on an existing DioxusUiRoot rerenders the root without remounting it.
+    pub fn with_props<P>(component: fn(Scope<P>) -> Element, props: P) -> Self
+    where
+        P: Properties + Clone + PartialEq + Send + Sync + 'static,
+    {
+        Self(Arc::new(RootComponent { component, props }))
+    }
+
+    fn is_same_component(&self, other: &DioxusUiRoot) -> bool {
+        self.0.is_same_component(&*other.0)
+    }
+}
+
+/// Values provided as dioxus context to the root component of a DioxusUiRoot on the same entity.
+#[derive(Component, Default, Clone)]
+pub struct DioxusUiContext {
+    values: HashMap<TypeId, Arc<dyn Fn(&ScopeState) + Send + Sync>>,
+}
+
+impl DioxusUiContext {
+    pub fn with<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.insert(value);
+        self
+    }
+
+    pub fn insert<T: Clone + Send + Sync + 'static>(&mut self, value: T) {
+        self.values.insert(
+            TypeId::of::<T>(),
+            Arc::new(move |scope: &ScopeState| {
+                scope.provide_context(value.clone());
+            }),
+        );
+    }
+
+    fn provide(&self, scope: &ScopeState) {
+        for provide_value in self.values.values() {
+            provide_value(scope);
+        }
+    }
+}
 
 #[derive(Default)]
 struct UiContext {
-    roots: HashMap<(Entity, DioxusUiRoot), UiRoot>,
-    subscriptions: EcsSubscriptions,
+    roots: EntityHashMap<Entity, UiRoot>,
+    compute_tasks: ComputeTasks,
+    global_signals: GlobalSignals,
+    query_registrations: QueryRegistrations,
 }
 
 struct UiRoot {
     virtual_dom: VirtualDom,
+    root_component: DioxusUiRoot,
+    root_props: Box<dyn Any>,
+    ecs_context: EcsContext,
+    subscriptions: EcsSubscriptions,
     element_id_to_bevy_ui_entity: HashMap<ElementId, Entity>,
     bevy_ui_entity_to_element_id: EntityHashMap<Entity, ElementId>,
     templates: HashMap<String, BevyTemplate>,
+    template_instances: EntityHashMap<Entity, TemplateInstance>,
+    /// The logical size of the root's window, which `vw` and `vh` are relative to.
+    viewport_size: Vec2,
     needs_rebuild: bool,
+    last_render: Option<Instant>,
 }
 
 impl UiRoot {
-    fn new(root_component: DioxusUiRoot) -> Self {
+    fn new(root_entity: Entity, root_component: DioxusUiRoot) -> Self {
+        let (virtual_dom, root_props) = root_component.0.new_virtual_dom();
+        let ecs_context = EcsContext::default();
+        let subscriptions = EcsSubscriptions::default();
+        let base_scope = virtual_dom.base_scope();
+        base_scope.provide_context(ecs_context.clone());
+        base_scope.provide_context(subscriptions.clone());
+        base_scope.provide_context(RootEntity(root_entity));
+
         Self {
-            virtual_dom: VirtualDom::new(root_component.0),
+            virtual_dom,
+            root_component,
+            root_props,
+            ecs_context,
+            subscriptions,
             element_id_to_bevy_ui_entity: HashMap::new(),
             bevy_ui_entity_to_element_id: EntityHashMap::default(),
             templates: HashMap::new(),
+            template_instances: EntityHashMap::default(),
+            viewport_size: Vec2::ZERO,
             needs_rebuild: true,
+            last_render: None,
+        }
+    }
+
+    /// Replaces the root component's props, marking the root dirty if they changed.
+    fn update_props(&mut self, root_component: DioxusUiRoot) {
+        if root_component.0.update_props(&*self.root_props) {
+            self.virtual_dom.mark_dirty(ScopeId(0));
+        }
+        self.root_component = root_component;
+    }
+
+    /// Swaps in a hot-reloaded template, rerendering the components that use it.
+    fn replace_template(&mut self, template: Template<'static>, world: &mut World) {
+        self.virtual_dom.replace_template(template);
+        let old_template = self.templates.insert(
+            template.name.to_owned(),
+            BevyTemplate::from_dioxus(
+                &template,
+                world.resource(),
+                world.resource(),
+                self.viewport_size,
+            ),
+        );
+
+        // Mounted instances of the old template no longer match its layout, so they can't be reused
+        if let Some(mut old_template) = old_template {
+            old_template.despawn_pool(world);
         }
+        self.template_instances
+            .retain(|_, template_instance| template_instance.template_name() != template.name);
     }
 }
 
@@ -117,17 +430,38 @@ macro_rules! impl_event {
 }
 
 pub mod prelude {
+    use bevy::render::color::Color;
     use dioxus::prelude::IntoAttributeValue;
 
+    /// Types that can be used as color attribute values, like
+    /// `background_color: Color::RED.into_attribute()`.
+    pub trait ColorExt: Sized {
+        fn into_bevy_color(self) -> Color;
 
-pub trait ColorExt: Sized {}
-    impl ColorExt for bevy::render::color::Color {
-        // TODO
+        fn into_attribute(self) -> AttributeColor<Self> {
+            AttributeColor(self)
+        }
+    }
+
+    impl ColorExt for Color {
+        fn into_bevy_color(self) -> Color {
+            self
+        }
     }
 
-    impl<'a, T: ColorExt> IntoAttributeValue<'a> for &'a  {
-        fn into_value(self, bump: &'a dioxus::core::exports::bumpalo::Bump) -> dioxus::core::AttributeValue<'a> {
-            
+    /// A color attribute value. Colors can't implement IntoAttributeValue directly, as both the
+    /// trait and bevy's Color are foreign to this crate.
+    pub struct AttributeColor<T: ColorExt>(pub T);
+
+    impl<'a, T: ColorExt> IntoAttributeValue<'a> for AttributeColor<T> {
+        fn into_value(
+            self,
+            bump: &'a dioxus::core::exports::bumpalo::Bump,
+        ) -> dioxus::core::AttributeValue<'a> {
+            let [r, g, b, a] = self.0.into_bevy_color().as_rgba_u8();
+            dioxus::core::AttributeValue::Text(
+                bump.alloc_str(&format!("#{r:02x}{g:02x}{b:02x}{a:02x}")),
+            )
         }
     }
 
@@ -145,6 +479,12 @@ pub trait ColorExt: Sized {}
             pub const flex_direction: AttributeDescription = ("flex-direction", None, false);
             pub const padding: AttributeDescription = ("padding", None, false);
             pub const background_color: AttributeDescription = ("background-color", None, false);
+            pub const border_width: AttributeDescription = ("border-width", None, false);
+            pub const border_color: AttributeDescription = ("border-color", None, false);
+            pub const color: AttributeDescription = ("color", None, false);
+            pub const font_size: AttributeDescription = ("font-size", None, false);
+            pub const data_testid: AttributeDescription = ("data-testid", None, false);
+            pub const class: AttributeDescription = ("class", None, false);
 
             // TODO: Many more attributes
         }
@@ -153,6 +493,14 @@ pub trait ColorExt: Sized {}
             impl_event! [
                 crate::events::PointerInput;
                 onclick
+                onmouseover
+                onmouseout
+            ];
+
+            impl_event! [
+                crate::events::KeyInput;
+                onkeydown
+                onkeyup
             ];
         }
     }
@@ -0,0 +1,119 @@
+use dioxus::{
+    core::{Element, Properties, Scope, VirtualDom},
+    prelude::render,
+};
+use std::{any::Any, cell::RefCell, rc::Rc};
+
+/// Type-erased root component and its props, as stored on a DioxusUiRoot.
+pub(crate) trait AnyRootComponent: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn is_same_component(&self, other: &dyn AnyRootComponent) -> bool;
+
+    /// Returns the VirtualDom along with the props cell that `update_props` writes into.
+    fn new_virtual_dom(&self) -> (VirtualDom, Box<dyn Any>);
+
+    /// Returns true if the props changed and the root needs to rerender.
+    fn update_props(&self, root_props: &dyn Any) -> bool;
+}
+
+pub(crate) struct RootComponent<P> {
+    pub component: fn(Scope<P>) -> Element,
+    pub props: P,
+}
+
+impl<P> AnyRootComponent for RootComponent<P>
+where
+    P: Properties + Clone + PartialEq + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn is_same_component(&self, other: &dyn AnyRootComponent) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Self>()
+            .is_some_and(|other| self.component as usize == other.component as usize)
+    }
+
+    fn new_virtual_dom(&self) -> (VirtualDom, Box<dyn Any>) {
+        let props = Rc::new(RefCell::new(self.props.clone()));
+        let virtual_dom = VirtualDom::new_with_props(
+            Root::<P>,
+            RootProps {
+                component: self.component,
+                props: Rc::clone(&props),
+            },
+        );
+        (virtual_dom, Box::new(props))
+    }
+
+    fn update_props(&self, root_props: &dyn Any) -> bool {
+        let mut props = root_props
+            .downcast_ref::<Rc<RefCell<P>>>()
+            .unwrap()
+            .borrow_mut();
+        if *props == self.props {
+            return false;
+        }
+        *props = self.props.clone();
+        true
+    }
+}
+
+struct RootProps<P> {
+    component: fn(Scope<P>) -> Element,
+    props: Rc<RefCell<P>>,
+}
+
+/// Renders the user's root component as a child, so that new props are diffed instead of remounting it.
+fn Root<P: Properties + Clone + 'static>(cx: Scope<RootProps<P>>) -> Element {
+    let props = cx.props.props.borrow().clone();
+    render! {
+        cx.component(cx.props.component, props, "DioxusUiRoot")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, testing::DioxusTestApp, DioxusUiRoot};
+    use dioxus::prelude::*;
+
+    #[derive(Props, PartialEq, Clone)]
+    struct CounterProps {
+        label: &'static str,
+    }
+
+    fn Counter(cx: Scope<CounterProps>) -> Element {
+        let label = cx.props.label;
+        let clicks = use_state(cx, || 0);
+        render! {
+            node { data_testid: "counter", onclick: move |_| clicks.modify(|clicks| clicks + 1), "{label}: {clicks}" }
+        }
+    }
+
+    #[test]
+    fn new_props_rerender_without_remounting() {
+        let mut app = DioxusTestApp::with_root(DioxusUiRoot::with_props(
+            Counter,
+            CounterProps { label: "Apples" },
+        ));
+        let counter = app.find_by_test_id("counter").unwrap();
+        app.click(counter);
+        let text = app.find_by_text("Apples: 1").unwrap();
+
+        let root_entity = app.root_entity();
+        app.world_mut()
+            .entity_mut(root_entity)
+            .insert(DioxusUiRoot::with_props(
+                Counter,
+                CounterProps { label: "Pears" },
+            ));
+        app.update();
+
+        // The same entities show the new props, and the component kept its state
+        assert_eq!(app.find_by_test_id("counter"), Some(counter));
+        assert_eq!(app.find_by_text("Pears: 1"), Some(text));
+    }
+}
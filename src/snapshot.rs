@@ -0,0 +1,250 @@
+use crate::UiContext;
+use bevy::{
+    ecs::{entity::Entity, world::World},
+    hierarchy::Children,
+    render::color::Color,
+    text::{Text, TextStyle},
+    ui::{BackgroundColor, BorderColor, Style},
+};
+use std::{env, fmt::Write, fs, path::Path};
+
+/// Serializes the UI tree below `root_entity` into a stable, indented text format. Nodes created by
+/// dioxus are labelled with their ElementId, and only Style fields, colors and text styles that
+/// differ from the default are written.
+pub fn ui_tree_snapshot(world: &World, root_entity: Entity) -> String {
+    let mut snapshot = String::new();
+    write_node(world, root_entity, 0, &mut snapshot);
+    snapshot
+}
+
+/// Compares the snapshot against the file at `path`, panicking with a line diff if they differ or
+/// if the file doesn't exist.
+///
+/// Run with `UPDATE_SNAPSHOTS=1` to write the file instead.
+pub fn assert_snapshot(snapshot: &str, path: impl AsRef<Path>) {
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some_and(|update| update == "1");
+    compare_snapshot(snapshot, path.as_ref(), update);
+}
+
+fn compare_snapshot(snapshot: &str, path: &Path, update: bool) {
+    if update {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, snapshot).unwrap();
+        return;
+    }
+    if !path.exists() {
+        panic!(
+            "UI snapshot `{}` doesn't exist, rerun with UPDATE_SNAPSHOTS=1 to create it:\n{snapshot}",
+            path.display()
+        );
+    }
+
+    let expected = fs::read_to_string(path).unwrap();
+    if expected != snapshot {
+        panic!(
+            "UI snapshot `{}` doesn't match, rerun with UPDATE_SNAPSHOTS=1 to accept the changes:\n{}",
+            path.display(),
+            line_diff(&expected, snapshot)
+        );
+    }
+}
+
+fn write_node(world: &World, entity: Entity, depth: usize, snapshot: &mut String) {
+    let element_id = world
+        .non_send_resource::<UiContext>()
+        .roots
+        .values()
+        .find_map(|ui_root| ui_root.bevy_ui_entity_to_element_id.get(&entity));
+
+    let indent = "  ".repeat(depth);
+    if let Some(text) = world.get::<Text>(entity) {
+        let value = text
+            .sections
+            .iter()
+            .map(|section| section.value.as_str())
+            .collect::<String>();
+        write!(snapshot, "{indent}text").unwrap();
+        if let Some(element_id) = element_id {
+            write!(snapshot, " #{}", element_id.0).unwrap();
+        }
+        write!(snapshot, " {value:?}").unwrap();
+        let mut attributes = Vec::new();
+        if let Some(section) = text.sections.first() {
+            let default = TextStyle::default();
+            if section.style.color != default.color {
+                attributes.push(format!("color: {}", hex(section.style.color)));
+            }
+            if section.style.font_size != default.font_size {
+                attributes.push(format!("font-size: {}", section.style.font_size));
+            }
+        }
+        if !attributes.is_empty() {
+            write!(snapshot, " {}", attributes.join(", ")).unwrap();
+        }
+        writeln!(snapshot).unwrap();
+    } else {
+        write!(snapshot, "{indent}node").unwrap();
+        if let Some(element_id) = element_id {
+            write!(snapshot, " #{}", element_id.0).unwrap();
+        }
+        let mut attributes = Vec::new();
+        if let Some(style) = world.get::<Style>(entity) {
+            style_attributes(style, &mut attributes);
+        }
+        if let Some(background_color) = world.get::<BackgroundColor>(entity) {
+            if background_color.0 != Color::NONE {
+                attributes.push(format!("background-color: {}", hex(background_color.0)));
+            }
+        }
+        if let Some(border_color) = world.get::<BorderColor>(entity) {
+            if border_color.0 != Color::NONE {
+                attributes.push(format!("border-color: {}", hex(border_color.0)));
+            }
+        }
+        if !attributes.is_empty() {
+            write!(snapshot, " {}", attributes.join(", ")).unwrap();
+        }
+        writeln!(snapshot).unwrap();
+    }
+
+    if let Some(children) = world.get::<Children>(entity) {
+        for child in children {
+            write_node(world, *child, depth + 1, snapshot);
+        }
+    }
+}
+
+fn style_attributes(style: &Style, attributes: &mut Vec<String>) {
+    let default = Style::default();
+    macro_rules! push_changed {
+        ($($name:literal => $field:ident),* $(,)?) => {
+            $(
+                if style.$field != default.$field {
+                    attributes.push(format!("{}: {:?}", $name, style.$field));
+                }
+            )*
+        };
+    }
+    push_changed! {
+        "display" => display,
+        "position" => position_type,
+        "flex-direction" => flex_direction,
+        "justify-content" => justify_content,
+        "align-content" => align_content,
+        "width" => width,
+        "height" => height,
+        "padding" => padding,
+        "border" => border,
+    }
+}
+
+fn hex(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+}
+
+/// A minimal line diff based on the longest common subsequence.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    let mut lcs = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            writeln!(diff, "  {}", expected[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if j < actual.len() && (i == expected.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            writeln!(diff, "+ {}", actual[j]).unwrap();
+            j += 1;
+        } else {
+            writeln!(diff, "- {}", expected[i]).unwrap();
+            i += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare_snapshot;
+    use crate::{prelude::*, testing::DioxusTestApp};
+    use dioxus::prelude::*;
+    use std::{env, fs, path::PathBuf};
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        let path = env::temp_dir()
+            .join(format!("bevy_dioxus_snapshots_{}", std::process::id()))
+            .join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't exist")]
+    fn fails_on_missing_snapshots() {
+        let path = snapshot_path("missing.txt");
+        compare_snapshot("node\n", &path, false);
+    }
+
+    #[test]
+    fn updates_snapshots() {
+        let path = snapshot_path("updated.txt");
+        compare_snapshot("node\n", &path, true);
+        compare_snapshot("node\n", &path, false);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "node\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't match")]
+    fn fails_on_changed_snapshots() {
+        let path = snapshot_path("changed.txt");
+        compare_snapshot("node\n", &path, true);
+        compare_snapshot("node\n  text \"Hi\"\n", &path, false);
+    }
+
+    #[test]
+    fn snapshots_mounted_components() {
+        fn Card(cx: Scope) -> Element {
+            let count = 1;
+            render! {
+                node {
+                    padding: "4px",
+                    border_width: "2px",
+                    border_color: "#ff0000",
+                    color: "#00ff00",
+                    font_size: "20",
+                    "Count: {count}"
+                }
+            }
+        }
+
+        let app = DioxusTestApp::new(Card);
+        let expected = [
+            "node #0",
+            "  node #1 \
+             padding: UiRect { left: Px(4.0), right: Px(4.0), top: Px(4.0), bottom: Px(4.0) }, \
+             border: UiRect { left: Px(2.0), right: Px(2.0), top: Px(2.0), bottom: Px(2.0) }, \
+             border-color: #ff0000ff",
+            "    text #2 \"Count: 1\" color: #00ff00ff, font-size: 20",
+        ];
+        assert_eq!(
+            app.snapshot(),
+            expected.map(|line| format!("{line}\n")).concat()
+        );
+    }
+}
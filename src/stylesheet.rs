@@ -0,0 +1,132 @@
+use crate::{
+    apply_mutations::{set_style_attribute, NodeStyle, CLASS_ATTRIBUTE},
+    theme::Theme,
+};
+use bevy::{
+    asset::Asset,
+    ecs::{component::Component, system::Resource},
+    math::Vec2,
+    reflect::TypePath,
+    utils::HashMap,
+};
+
+/// Named lists of style declarations, applied to nodes through the `class` attribute.
+///
+/// A node's classes are applied in order, then its own style attributes, so later classes and
+/// inline attributes win. Unknown classes are ignored, like in CSS. Changing the stylesheet
+/// restyles every node that uses a class. Stylesheets can also be loaded from `.css` files, see
+/// CssLoader.
+#[derive(Resource, Asset, TypePath, Default, Clone)]
+pub struct DioxusStylesheet {
+    classes: HashMap<String, Vec<(String, String)>>,
+}
+
+impl DioxusStylesheet {
+    pub fn with_class(
+        mut self,
+        name: impl Into<String>,
+        declarations: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.insert_class(name, declarations);
+        self
+    }
+
+    pub fn insert_class(
+        &mut self,
+        name: impl Into<String>,
+        declarations: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) {
+        self.classes.insert(
+            name.into(),
+            declarations
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        );
+    }
+
+    /// Appends declarations to a class, overriding its existing declarations of the same attributes.
+    pub fn extend_class(
+        &mut self,
+        name: impl Into<String>,
+        declarations: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) {
+        self.classes.entry(name.into()).or_default().extend(
+            declarations
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+    }
+
+    pub fn remove_class(&mut self, name: &str) {
+        self.classes.remove(name);
+    }
+
+    pub fn get_class(&self, name: &str) -> Option<&[(String, String)]> {
+        self.classes.get(name).map(Vec::as_slice)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = (&str, &[(String, String)])> {
+        self.classes
+            .iter()
+            .map(|(name, declarations)| (name.as_str(), declarations.as_slice()))
+    }
+}
+
+/// The class and style attributes of a node that uses classes, theme tokens, viewport units or has
+/// dynamic attributes, so its style can be resolved again when any of them, the stylesheet, the
+/// theme or its root's window size changes.
+#[derive(Component, Default, Clone)]
+pub struct StyleAttributes {
+    pub class: String,
+    pub inline: Vec<(String, String)>,
+}
+
+impl StyleAttributes {
+    pub fn set(&mut self, name: &str, value: &str) {
+        if name == CLASS_ATTRIBUTE {
+            self.class = value.to_owned();
+            return;
+        }
+        match self
+            .inline
+            .iter_mut()
+            .find(|(existing_name, _)| existing_name == name)
+        {
+            Some((_, existing_value)) => *existing_value = value.to_owned(),
+            None => self.inline.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    pub fn uses_theme_tokens(&self) -> bool {
+        self.inline
+            .iter()
+            .any(|(_, value)| value.starts_with("var("))
+    }
+
+    pub fn uses_viewport_units(&self) -> bool {
+        self.inline
+            .iter()
+            .any(|(_, value)| value.ends_with("vw") || value.ends_with("vh"))
+    }
+
+    pub fn resolve(
+        &self,
+        stylesheet: &DioxusStylesheet,
+        theme: &Theme,
+        viewport_size: Vec2,
+    ) -> NodeStyle {
+        let mut node_style = NodeStyle::default();
+
+        let class_declarations = self
+            .class
+            .split_whitespace()
+            .filter_map(|class| stylesheet.get_class(class))
+            .flatten();
+        for (name, value) in class_declarations.chain(&self.inline) {
+            set_style_attribute(name, value, theme, viewport_size, &mut node_style);
+        }
+
+        node_style
+    }
+}
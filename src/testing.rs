@@ -0,0 +1,271 @@
+use crate::{
+    DioxusUiBundle, DioxusUiPlugin, DioxusUiRoot, DioxusUiTemplateUpdates, DioxusUiTestId,
+};
+use bevy::{
+    app::App,
+    asset::{AssetApp, AssetPlugin},
+    ecs::{entity::Entity, query::With, world::World},
+    hierarchy::{Children, HierarchyPlugin},
+    input::{
+        keyboard::{KeyCode, KeyboardInput},
+        ButtonState, InputPlugin,
+    },
+    math::Vec3,
+    render::{camera::NormalizedRenderTarget, texture::ImagePlugin},
+    sprite::TextureAtlas,
+    text::{Text, TextPlugin},
+    transform::{components::GlobalTransform, TransformPlugin},
+    ui::{node_bundles::NodeBundle, UiPlugin},
+    utils::Duration,
+    window::{PrimaryWindow, WindowPlugin, WindowRef},
+    MinimalPlugins,
+};
+use bevy_mod_picking::{
+    backend::HitData,
+    events::{Click, Out, Over, Pointer},
+    pointer::{Location, PointerButton, PointerId},
+};
+use dioxus::core::{Element, Scope, Template};
+use std::path::Path;
+
+pub use crate::snapshot::{assert_snapshot, ui_tree_snapshot};
+
+/// A headless app that mounts a single DioxusUiRoot and lays it out, without a window or GPU.
+pub struct DioxusTestApp {
+    pub app: App,
+    root_entity: Entity,
+    hovered: Option<Entity>,
+}
+
+impl DioxusTestApp {
+    pub fn new(root_component: fn(Scope) -> Element) -> Self {
+        Self::with_root(DioxusUiRoot::new(root_component))
+    }
+
+    pub fn with_root(dioxus_ui_root: DioxusUiRoot) -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            InputPlugin,
+            WindowPlugin::default(),
+            AssetPlugin::default(),
+            ImagePlugin::default(),
+            TextPlugin,
+            UiPlugin,
+            DioxusUiPlugin::default(),
+        ))
+        .init_asset::<TextureAtlas>()
+        .add_event::<Pointer<Click>>()
+        .add_event::<Pointer<Over>>()
+        .add_event::<Pointer<Out>>();
+
+        let mut test_app = Self {
+            app,
+            root_entity: Entity::PLACEHOLDER,
+            hovered: None,
+        };
+        test_app.spawn_root(dioxus_ui_root);
+        test_app
+    }
+
+    /// Spawns a new root entity and runs a frame to mount it. Finding nodes and snapshots use the
+    /// new root from then on.
+    pub fn spawn_root(&mut self, dioxus_ui_root: DioxusUiRoot) -> Entity {
+        self.root_entity = self
+            .app
+            .world
+            .spawn(DioxusUiBundle {
+                dioxus_ui_root,
+                node_bundle: NodeBundle::default(),
+            })
+            .id();
+        self.update();
+        self.root_entity
+    }
+
+    /// Despawns the root entity, without its children, and runs a frame to tear down its UI.
+    pub fn despawn_root(&mut self) {
+        self.app.world.despawn(self.root_entity);
+        self.update();
+    }
+
+    /// Runs a single frame.
+    pub fn update(&mut self) {
+        self.app.update();
+    }
+
+    pub fn update_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    pub fn root_entity(&self) -> Entity {
+        self.root_entity
+    }
+
+    /// Returns the first node below the root, depth first, whose text equals `text`.
+    pub fn find_by_text(&self, text: &str) -> Option<Entity> {
+        self.find(|world, entity| self::text(world, entity).is_some_and(|value| value == text))
+    }
+
+    /// Returns the first node below the root, depth first, with a matching `data-testid` attribute.
+    pub fn find_by_test_id(&self, test_id: &str) -> Option<Entity> {
+        self.find(|world, entity| {
+            world
+                .get::<DioxusUiTestId>(entity)
+                .is_some_and(|DioxusUiTestId(value)| value == test_id)
+        })
+    }
+
+    /// Returns the text of a text node, joining all of its sections.
+    pub fn text(&self, entity: Entity) -> Option<String> {
+        text(&self.app.world, entity)
+    }
+
+    /// Serializes the root's UI tree, see `ui_tree_snapshot`.
+    pub fn snapshot(&self) -> String {
+        ui_tree_snapshot(&self.app.world, self.root_entity)
+    }
+
+    /// Compares the root's UI tree against a stored snapshot file, see `assert_snapshot`.
+    pub fn assert_snapshot(&self, path: impl AsRef<Path>) {
+        assert_snapshot(&self.snapshot(), path);
+    }
+
+    /// Pushes a hot-reloaded rsx template, as the hot-reload server would, and runs a frame to
+    /// apply it.
+    pub fn replace_template(&mut self, template: Template<'static>) {
+        self.app
+            .world
+            .resource::<DioxusUiTemplateUpdates>()
+            .sender()
+            .send(template)
+            .unwrap();
+        self.update();
+    }
+
+    /// Sends a click on `target`, as the picking backend would, and runs a frame to handle it.
+    pub fn click(&mut self, target: Entity) {
+        let (_, position) = self.pointer_location(target);
+        self.click_at(target, position);
+    }
+
+    /// Sends a click that hits `target` at the world-space `position`, like a raycast on a mesh
+    /// with a DioxusUiSurface would, and runs a frame to handle it.
+    pub fn click_at(&mut self, target: Entity, position: Vec3) {
+        let (location, _) = self.pointer_location(target);
+        self.app.world.send_event(Pointer::new(
+            PointerId::Mouse,
+            location,
+            target,
+            Click {
+                button: PointerButton::Primary,
+                hit: HitData::new(Entity::PLACEHOLDER, 0.0, Some(position), None),
+                duration: Duration::ZERO,
+            },
+        ));
+        self.update();
+    }
+
+    /// Moves the pointer onto `target`, sending an out event for the previously hovered node and
+    /// an over event for `target`, and runs a frame to handle them.
+    pub fn hover(&mut self, target: Entity) {
+        if let Some(previous) = self.hovered.replace(target) {
+            let (location, position) = self.pointer_location(previous);
+            self.app.world.send_event(Pointer::new(
+                PointerId::Mouse,
+                location,
+                previous,
+                Out {
+                    hit: HitData::new(Entity::PLACEHOLDER, 0.0, Some(position), None),
+                },
+            ));
+        }
+
+        let (location, position) = self.pointer_location(target);
+        self.app.world.send_event(Pointer::new(
+            PointerId::Mouse,
+            location,
+            target,
+            Over {
+                hit: HitData::new(Entity::PLACEHOLDER, 0.0, Some(position), None),
+            },
+        ));
+        self.update();
+    }
+
+    /// Presses and releases `key_code` in the primary window, and runs a frame to handle it.
+    /// Keyboard events go to the hovered node, see `hover`.
+    pub fn press_key(&mut self, key_code: KeyCode) {
+        let window = self.primary_window();
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            self.app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state,
+                window,
+            });
+        }
+        self.update();
+    }
+
+    fn primary_window(&mut self) -> Entity {
+        let world = &mut self.app.world;
+        world
+            .query_filtered::<Entity, With<PrimaryWindow>>()
+            .single(world)
+    }
+
+    fn pointer_location(&mut self, target: Entity) -> (Location, Vec3) {
+        let primary_window = self.primary_window();
+        let position = self
+            .app
+            .world
+            .get::<GlobalTransform>(target)
+            .map(GlobalTransform::translation)
+            .unwrap_or(Vec3::ZERO);
+
+        let location = Location {
+            target: NormalizedRenderTarget::Window(
+                WindowRef::Primary.normalize(Some(primary_window)).unwrap(),
+            ),
+            position: position.truncate(),
+        };
+        (location, position)
+    }
+
+    fn find(&self, predicate: impl Fn(&World, Entity) -> bool) -> Option<Entity> {
+        let world = &self.app.world;
+        let mut stack = vec![self.root_entity];
+        while let Some(entity) = stack.pop() {
+            if entity != self.root_entity && predicate(world, entity) {
+                return Some(entity);
+            }
+            if let Some(children) = world.get::<Children>(entity) {
+                stack.extend(children.iter().rev());
+            }
+        }
+        None
+    }
+}
+
+fn text(world: &World, entity: Entity) -> Option<String> {
+    let text = world.get::<Text>(entity)?;
+    Some(
+        text.sections
+            .iter()
+            .map(|section| section.value.as_str())
+            .collect(),
+    )
+}
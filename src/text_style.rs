@@ -0,0 +1,132 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Or, With},
+        system::Query,
+    },
+    hierarchy::{Children, Parent},
+    render::color::Color,
+    text::{Text, TextStyle},
+};
+
+/// The `color` and `font-size` attributes of a node. Like in CSS, text below the node uses them
+/// unless a closer node sets them as well.
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug)]
+pub struct InheritedTextStyle {
+    pub color: Option<Color>,
+    pub font_size: Option<f32>,
+}
+
+/// Restyles the text below nodes whose InheritedTextStyle changed, and text that was added, moved
+/// or replaced.
+pub fn inherit_text_styles(
+    changed_nodes: Query<Entity, Changed<InheritedTextStyle>>,
+    changed_texts: Query<Entity, (With<Text>, Or<(Changed<Text>, Changed<Parent>)>)>,
+    children: Query<&Children>,
+    parents: Query<&Parent>,
+    inherited_text_styles: Query<&InheritedTextStyle>,
+    mut texts: Query<&mut Text>,
+) {
+    let mut stack = changed_nodes.iter().collect::<Vec<_>>();
+    stack.extend(changed_texts.iter());
+
+    while let Some(entity) = stack.pop() {
+        if texts.contains(entity) {
+            let (color, font_size) = resolve_text_style(entity, &parents, &inherited_text_styles);
+            let mut text = texts.get_mut(entity).unwrap();
+            let needs_update = text.sections.iter().any(|section| {
+                section.style.color != color || section.style.font_size != font_size
+            });
+            if needs_update {
+                for section in &mut text.sections {
+                    section.style.color = color;
+                    section.style.font_size = font_size;
+                }
+            }
+        }
+        if let Ok(children) = children.get(entity) {
+            stack.extend(children.iter());
+        }
+    }
+}
+
+/// Returns the color and font size set by the closest ancestors of a text entity.
+fn resolve_text_style(
+    mut entity: Entity,
+    parents: &Query<&Parent>,
+    inherited_text_styles: &Query<&InheritedTextStyle>,
+) -> (Color, f32) {
+    let default = TextStyle::default();
+    let (mut color, mut font_size) = (None, None);
+    while let Ok(parent) = parents.get(entity) {
+        entity = parent.get();
+        if let Ok(inherited_text_style) = inherited_text_styles.get(entity) {
+            color = color.or(inherited_text_style.color);
+            font_size = font_size.or(inherited_text_style.font_size);
+        }
+        if color.is_some() && font_size.is_some() {
+            break;
+        }
+    }
+    (
+        color.unwrap_or(default.color),
+        font_size.unwrap_or(default.font_size),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, testing::DioxusTestApp};
+    use bevy::{render::color::Color, text::Text};
+    use dioxus::prelude::*;
+
+    #[test]
+    fn text_inherits_the_closest_color() {
+        fn Colors(cx: Scope) -> Element {
+            let blue = use_state(cx, || false);
+            render! {
+                node {
+                    data_testid: "outer",
+                    color: if **blue { "blue" } else { "red" },
+                    onclick: move |_| blue.set(true),
+                    node { "Outer" }
+                    node { color: "#00ff00", "Inner" }
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Colors);
+        let color = |app: &DioxusTestApp, text: &str| {
+            let entity = app.find_by_text(text).unwrap();
+            app.world().get::<Text>(entity).unwrap().sections[0]
+                .style
+                .color
+        };
+        assert_eq!(color(&app, "Outer"), Color::RED);
+        assert_eq!(color(&app, "Inner"), Color::GREEN);
+
+        let outer = app.find_by_test_id("outer").unwrap();
+        app.click(outer);
+        assert_eq!(color(&app, "Outer"), Color::BLUE);
+        assert_eq!(color(&app, "Inner"), Color::GREEN);
+    }
+
+    #[test]
+    fn text_uses_font_size_tokens() {
+        fn Sizes(cx: Scope) -> Element {
+            render! {
+                node { font_size: "var(--text-lg)", "Large" }
+            }
+        }
+
+        let app = DioxusTestApp::new(Sizes);
+        let entity = app.find_by_text("Large").unwrap();
+        assert_eq!(
+            app.world().get::<Text>(entity).unwrap().sections[0]
+                .style
+                .font_size,
+            18.0
+        );
+    }
+}
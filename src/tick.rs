@@ -1,124 +1,1191 @@
 use crate::{
-    apply_mutations::apply_mutations, deferred_system::DeferredSystemRegistry,
-    events::EventReaders, hooks::EcsContext, DioxusUiRoot, UiContext, UiRoot,
+    apply_mutations::{apply_mutations, MutationTarget},
+    async_world::AsyncWorldQueue,
+    deferred_commands::DeferredCommandQueue,
+    deferred_system::DeferredSystemRegistry,
+    events::EventReaders,
+    hooks::register_queries,
+    stylesheet::StyleAttributes,
+    theme::Theme,
+    DioxusStylesheet, DioxusUiContext, DioxusUiPaused, DioxusUiRenderBudget, DioxusUiRoot,
+    DioxusUiTargetCamera, DioxusUiTemplateUpdates, DioxusUiUpdateRate, UiContext, UiRoot,
 };
+#[cfg(debug_assertions)]
+use bevy::ecs::component::{ComponentTicks, Tick};
 use bevy::{
     ecs::{
         entity::Entity,
+        query::With,
+        system::Command,
         world::{Mut, World},
     },
-    hierarchy::Parent,
+    hierarchy::{Children, DespawnRecursive, Parent},
+    math::Vec2,
+    render::{
+        camera::{Camera, RenderTarget},
+        view::Visibility,
+    },
     text::Text,
-    utils::HashMap,
+    ui::UiScale,
+    utils::{EntityHashMap, HashSet, Instant},
+    window::{PrimaryWindow, Window, WindowRef},
+};
+use dioxus::core::ElementId;
+use std::{
+    any::{Any, TypeId},
+    mem,
+    rc::Rc,
 };
-use std::{any::Any, mem, rc::Rc, sync::Arc};
 
-pub fn tick_dioxus_ui(world: &mut World) {
+/// Runs work queued from dioxus components since the last frame.
+pub fn run_deferred_ui_work(world: &mut World) {
     run_deferred_systems(world);
+    apply_deferred_commands(world);
+    run_async_world_requests(world);
+    poll_compute_tasks(world);
+}
 
-    let ui_events = world.resource_scope(|world, mut event_readers: Mut<EventReaders>| {
-        event_readers.get_dioxus_events(world.resource())
-    });
-
-    let root_entities: HashMap<Entity, DioxusUiRoot> = world
+/// Creates, updates and tears down UiRoots to match the DioxusUiRoot entities, then dispatches
+/// picking events to them.
+pub fn dispatch_ui_events(world: &mut World) {
+    let root_entities: EntityHashMap<Entity, DioxusUiRoot> = world
         .query::<(Entity, &DioxusUiRoot)>()
         .iter(world)
-        .map(|(entity, ui_root)| (entity, *ui_root))
+        .map(|(entity, ui_root)| (entity, ui_root.clone()))
         .collect();
-    let mut ui_roots = mem::take(&mut world.non_send_resource_mut::<UiContext>().roots);
 
-    for (root_entity, dioxus_ui_root) in root_entities {
-        let mut ui_root = ui_roots
-            .remove(&(root_entity, dioxus_ui_root))
-            .unwrap_or_else(|| UiRoot::new(dioxus_ui_root));
+    teardown_removed_ui_roots(&root_entities, world);
+
+    let ui_events = world.resource_scope(|world, mut event_readers: Mut<EventReaders>| {
+        event_readers.get_dioxus_events(world)
+    });
 
-        dispatch_ui_events(&ui_events, &mut ui_root, world);
+    let mut ui_roots = mem::take(&mut world.non_send_resource_mut::<UiContext>().roots);
 
-        schedule_ui_renders_from_ecs_subscriptions(&mut ui_root, world);
+    for (root_entity, dioxus_ui_root) in root_entities {
+        let mut ui_root = match ui_roots.remove(&root_entity) {
+            Some(mut ui_root) => {
+                ui_root.update_props(dioxus_ui_root);
+                ui_root
+            }
+            None => UiRoot::new(root_entity, dioxus_ui_root),
+        };
 
-        render_ui(root_entity, &mut ui_root, world);
+        if !is_paused(root_entity, world) {
+            dispatch_ui_events_to_root(&ui_events, &mut ui_root, world);
+        }
 
         world
             .non_send_resource_mut::<UiContext>()
             .roots
-            .insert((root_entity, dioxus_ui_root), ui_root);
+            .insert(root_entity, ui_root);
     }
 }
 
-fn run_deferred_systems(world: &mut World) {
-    for system_id in mem::take(&mut *world.resource_mut::<DeferredSystemRegistry>().run_queue) {
-        let _ = world.run_system(system_id);
+/// Rerenders dirty components of every UiRoot and applies the mutations to the bevy UI.
+pub fn render_ui(world: &mut World) {
+    let changed_global_signals = world
+        .non_send_resource::<UiContext>()
+        .global_signals
+        .take_changed();
+
+    let render_budget = world.resource::<DioxusUiRenderBudget>().0;
+    let frame_start = Instant::now();
+
+    let template_updates = world.resource::<DioxusUiTemplateUpdates>().take();
+
+    let mut ui_roots = mem::take(&mut world.non_send_resource_mut::<UiContext>().roots);
+
+    for ui_root in ui_roots.values_mut() {
+        for template in &template_updates {
+            ui_root.replace_template(*template, world);
+        }
+    }
+
+    let restyle_all =
+        world.is_resource_changed::<DioxusStylesheet>() || world.is_resource_changed::<Theme>();
+    for (root_entity, ui_root) in ui_roots.iter_mut() {
+        let viewport_size = viewport_size(*root_entity, world);
+        if restyle_all || viewport_size != ui_root.viewport_size {
+            ui_root.viewport_size = viewport_size;
+            restyle_ui_root(*root_entity, ui_root, world);
+        }
+    }
+
+    // Roots that waited the longest render first, so a render budget doesn't starve any root
+    let mut render_order = ui_roots
+        .iter()
+        .map(|(root_entity, ui_root)| (ui_root.last_render, *root_entity))
+        .collect::<Vec<_>>();
+    render_order.sort_unstable();
+
+    for (last_render, root_entity) in render_order {
+        // Despawned since DioxusUiSet::DispatchEvents, it will be torn down next frame
+        if world.get_entity(root_entity).is_none() {
+            continue;
+        }
+        let ui_root = ui_roots.get_mut(&root_entity).unwrap();
+
+        // Components are marked dirty even if the root doesn't render this frame, so no changes are missed
+        schedule_ui_renders_from_ecs_subscriptions(ui_root, &changed_global_signals, world);
+
+        if is_paused(root_entity, world) {
+            continue;
+        }
+        if let (Some(last_render), Some(DioxusUiUpdateRate(update_rate))) =
+            (last_render, world.get::<DioxusUiUpdateRate>(root_entity))
+        {
+            if *update_rate > 0.0 && last_render.elapsed().as_secs_f32() < update_rate.recip() {
+                continue;
+            }
+        }
+        if render_budget.is_some_and(|render_budget| frame_start.elapsed() >= render_budget) {
+            continue;
+        }
+
+        render_ui_root(root_entity, ui_root, world);
+        ui_root.last_render = Some(Instant::now());
     }
 
-    world.resource_scope(|world, mut system_registry: Mut<DeferredSystemRegistry>| {
-        system_registry.ref_counts.retain(|system_id, ref_count| {
-            let cleanup = Arc::strong_count(ref_count) == 1;
-            if cleanup {
-                world.remove_system(*system_id).unwrap();
+    world.non_send_resource_mut::<UiContext>().roots = ui_roots;
+}
+
+/// Re-resolves the styles of a root's templates and of its nodes with classes, theme tokens,
+/// viewport units or dynamic attributes.
+fn restyle_ui_root(root_entity: Entity, ui_root: &mut UiRoot, world: &mut World) {
+    let viewport_size = ui_root.viewport_size;
+    world.resource_scope(|world, stylesheet: Mut<DioxusStylesheet>| {
+        world.resource_scope(|world, theme: Mut<Theme>| {
+            for template in ui_root.templates.values_mut() {
+                template.restyle(&stylesheet, &theme, viewport_size);
+            }
+
+            // Parked template instances aren't in the hierarchy, they're restyled when they're reused
+            let mut stack = vec![root_entity];
+            while let Some(entity) = stack.pop() {
+                if let Some(style_attributes) = world.get::<StyleAttributes>(entity) {
+                    let node_style = style_attributes.resolve(&stylesheet, &theme, viewport_size);
+                    node_style.apply(&mut world.entity_mut(entity));
+                }
+                if let Some(children) = world.get::<Children>(entity) {
+                    stack.extend(children.iter());
+                }
             }
-            !cleanup
         });
     });
 }
 
-fn dispatch_ui_events(
+/// The logical size of the window that a root is shown in, see DioxusUiTargetCamera, in the
+/// units of Val::Px. bevy_ui scales those by UiScale, so the size is divided by it.
+fn viewport_size(root_entity: Entity, world: &mut World) -> Vec2 {
+    let ui_scale = world
+        .get_resource::<UiScale>()
+        .map_or(1.0, |ui_scale| ui_scale.0 as f32);
+    window_size(root_entity, world) / ui_scale
+}
+
+fn window_size(root_entity: Entity, world: &mut World) -> Vec2 {
+    let target_window = world
+        .get::<DioxusUiTargetCamera>(root_entity)
+        .and_then(|DioxusUiTargetCamera(camera)| world.get::<Camera>(*camera))
+        .and_then(|camera| match camera.target {
+            RenderTarget::Window(WindowRef::Entity(window)) => Some(window),
+            _ => None,
+        });
+    if let Some(window) = target_window.and_then(|window| world.get::<Window>(window)) {
+        return Vec2::new(window.width(), window.height());
+    }
+
+    world
+        .query_filtered::<&Window, With<PrimaryWindow>>()
+        .get_single(world)
+        .map_or(Vec2::ZERO, |window| {
+            Vec2::new(window.width(), window.height())
+        })
+}
+
+/// Paused roots keep their UiRoot, but don't receive events or render until they're resumed.
+fn is_paused(root_entity: Entity, world: &World) -> bool {
+    world.entity(root_entity).contains::<DioxusUiPaused>() || is_hidden(root_entity, world)
+}
+
+/// Resolves an entity's Visibility through its ancestors, like bevy's visibility propagation does
+/// in PostUpdate, so hiding a root or one of its ancestors pauses it in the same frame.
+fn is_hidden(mut entity: Entity, world: &World) -> bool {
+    loop {
+        match world.get::<Visibility>(entity) {
+            Some(Visibility::Hidden) => return true,
+            Some(Visibility::Visible) => return false,
+            Some(Visibility::Inherited) | None => {}
+        }
+        match world.get::<Parent>(entity) {
+            Some(parent) => entity = parent.get(),
+            None => return false,
+        }
+    }
+}
+
+/// Tears down UiRoots whose entity was despawned or no longer has the same DioxusUiRoot.
+///
+/// Systems of the root still run if they were scheduled, and are dropped after the next
+/// `run_deferred_systems`.
+fn teardown_removed_ui_roots(
+    root_entities: &EntityHashMap<Entity, DioxusUiRoot>,
+    world: &mut World,
+) {
+    let removed_ui_roots = world
+        .non_send_resource_mut::<UiContext>()
+        .roots
+        .extract_if(|root_entity, ui_root| {
+            !root_entities
+                .get(root_entity)
+                .is_some_and(|dioxus_ui_root| {
+                    dioxus_ui_root.is_same_component(&ui_root.root_component)
+                })
+        })
+        .collect::<Vec<_>>();
+
+    for (root_entity, ui_root) in removed_ui_roots {
+        let UiRoot {
+            virtual_dom,
+            element_id_to_bevy_ui_entity,
+            mut templates,
+            ..
+        } = ui_root;
+
+        // Dropping the VirtualDom drops every scope's hooks, which removes subscriptions, releases
+        // systems and cancels compute tasks
+        drop(virtual_dom);
+
+        for entity in element_id_to_bevy_ui_entity.into_values() {
+            if entity != root_entity && world.get_entity(entity).is_some() {
+                DespawnRecursive { entity }.apply(world);
+            }
+        }
+        for template in templates.values_mut() {
+            template.despawn_pool(world);
+        }
+    }
+}
+
+fn run_deferred_systems(world: &mut World) {
+    let system_registry = world.resource::<DeferredSystemRegistry>();
+    let run_queue = mem::take(&mut *system_registry.run_queue.lock().unwrap());
+    let removal_queue = mem::take(&mut *system_registry.removal_queue.lock().unwrap());
+
+    for system in run_queue {
+        if let Some(system) = system.lock().unwrap().as_mut() {
+            system.run(world);
+        }
+    }
+
+    for system in removal_queue {
+        system.lock().unwrap().take();
+    }
+}
+
+fn apply_deferred_commands(world: &mut World) {
+    let mut command_queue = world.resource::<DeferredCommandQueue>().take();
+    command_queue.apply(world);
+}
+
+fn run_async_world_requests(world: &mut World) {
+    for request in world.resource::<AsyncWorldQueue>().take() {
+        request(world);
+    }
+}
+
+fn poll_compute_tasks(world: &World) {
+    world
+        .non_send_resource::<UiContext>()
+        .compute_tasks
+        .borrow_mut()
+        .retain_mut(|poll| poll());
+}
+
+fn dispatch_ui_events_to_root(
     events: &Vec<(Entity, &str, Rc<dyn Any>)>,
     ui_root: &mut UiRoot,
     world: &World,
 ) {
-    for (mut target, name, data) in events {
-        let mut target_element_id = ui_root.bevy_ui_entity_to_element_id.get(&target).copied();
-        while target_element_id.is_none() || world.entity(target).contains::<Text>() {
-            target = world.entity(target).get::<Parent>().unwrap().get();
-            target_element_id = ui_root.bevy_ui_entity_to_element_id.get(&target).copied();
+    let events = events
+        .iter()
+        .filter_map(|(target, name, data)| {
+            Some((find_event_target(*target, ui_root, world)?, *name, data))
+        })
+        .collect::<Vec<_>>();
+
+    // Listeners can read the World as well, through the hooks of their component
+    ui_root.ecs_context.with_world(world, || {
+        for (target_element_id, name, data) in events {
+            ui_root
+                .virtual_dom
+                .handle_event(name, Rc::clone(data), target_element_id, true);
         }
+    });
+}
 
-        ui_root
-            .virtual_dom
-            .handle_event(name, Rc::clone(data), target_element_id.unwrap(), true);
+/// Returns the element of the closest non-text ancestor of `target` that belongs to this root, or
+/// None if `target` is part of another root.
+fn find_event_target(mut target: Entity, ui_root: &UiRoot, world: &World) -> Option<ElementId> {
+    loop {
+        let entity = world.get_entity(target)?;
+        if !entity.contains::<Text>() {
+            if let Some(element_id) = ui_root.bevy_ui_entity_to_element_id.get(&target) {
+                return Some(*element_id);
+            }
+        }
+        target = entity.get::<Parent>()?.get();
     }
 }
 
-fn schedule_ui_renders_from_ecs_subscriptions(ui_root: &mut UiRoot, world: &World) {
-    let ecs_subscriptions = &world.non_send_resource::<UiContext>().subscriptions;
+fn schedule_ui_renders_from_ecs_subscriptions(
+    ui_root: &mut UiRoot,
+    changed_global_signals: &HashSet<TypeId>,
+    world: &World,
+) {
+    let ecs_subscriptions = &ui_root.subscriptions;
 
-    for scope_id in &*ecs_subscriptions.world_and_queries {
+    for scope_id in &*ecs_subscriptions.world_and_queries.borrow() {
         ui_root.virtual_dom.mark_dirty(*scope_id);
     }
 
-    for (resource_id, scope_ids) in &*ecs_subscriptions.resources {
+    for (resource_id, scope_ids) in &*ecs_subscriptions.resources.borrow() {
         if world.is_resource_changed_by_id(*resource_id) {
             for scope_id in scope_ids {
                 ui_root.virtual_dom.mark_dirty(*scope_id);
             }
         }
     }
+
+    for (signal_id, scope_ids) in &*ecs_subscriptions.global_signals.borrow() {
+        if changed_global_signals.contains(signal_id) {
+            for scope_id in scope_ids {
+                ui_root.virtual_dom.mark_dirty(*scope_id);
+            }
+        }
+    }
 }
 
-fn render_ui(root_entity: Entity, ui_root: &mut UiRoot, world: &mut World) {
-    ui_root
-        .virtual_dom
-        .base_scope()
-        .provide_context(EcsContext { world });
+fn render_ui_root(root_entity: Entity, ui_root: &mut UiRoot, world: &mut World) {
+    if let Some(ui_context) = world.get::<DioxusUiContext>(root_entity) {
+        ui_context.provide(ui_root.virtual_dom.base_scope());
+    }
 
     if ui_root.needs_rebuild {
-        apply_mutations(
-            ui_root.virtual_dom.rebuild(),
-            &mut ui_root.element_id_to_bevy_ui_entity,
-            &mut ui_root.bevy_ui_entity_to_element_id,
-            &mut ui_root.templates,
-            root_entity,
-            world,
-        );
+        render_and_apply_mutations(root_entity, ui_root, true, world);
         ui_root.needs_rebuild = false;
     }
+    render_and_apply_mutations(root_entity, ui_root, false, world);
+
+    // Components whose queries were just registered rerender with the query's results
+    while register_queries(world) {
+        render_and_apply_mutations(root_entity, ui_root, false, world);
+    }
+}
+
+/// Rebuilds or rerenders the dirty components with read-only access to the World, then applies the
+/// mutations.
+fn render_and_apply_mutations(
+    root_entity: Entity,
+    ui_root: &mut UiRoot,
+    rebuild: bool,
+    world: &mut World,
+) {
+    #[cfg(debug_assertions)]
+    let world_before_render = begin_read_only_check(world);
+
+    let mutations = ui_root.ecs_context.with_world(world, || {
+        if rebuild {
+            ui_root.virtual_dom.rebuild()
+        } else {
+            ui_root.virtual_dom.render_immediate()
+        }
+    });
 
-    apply_mutations(
-        ui_root.virtual_dom.render_immediate(),
-        &mut ui_root.element_id_to_bevy_ui_entity,
-        &mut ui_root.bevy_ui_entity_to_element_id,
-        &mut ui_root.templates,
+    #[cfg(debug_assertions)]
+    assert_render_was_read_only(world_before_render, world);
+
+    let target = MutationTarget {
+        element_id_to_bevy_ui_entity: &mut ui_root.element_id_to_bevy_ui_entity,
+        bevy_ui_entity_to_element_id: &mut ui_root.bevy_ui_entity_to_element_id,
+        templates: &mut ui_root.templates,
+        template_instances: &mut ui_root.template_instances,
         root_entity,
-        world,
+        viewport_size: ui_root.viewport_size,
+    };
+    apply_mutations(mutations, target, world);
+}
+
+/// Starts a new change tick, so that writes during the render can be told apart from earlier ones,
+/// and returns it along with the entity count.
+#[cfg(debug_assertions)]
+fn begin_read_only_check(world: &World) -> (Tick, u32) {
+    (world.increment_change_tick(), world.entities().len())
+}
+
+/// Panics if any resource was changed, or any entity spawned or despawned, since
+/// `begin_read_only_check`.
+///
+/// Components are only checked with the `check_render_component_writes` feature, as that walks
+/// every component of every entity after each render.
+#[cfg(debug_assertions)]
+fn assert_render_was_read_only((before_render, entity_count): (Tick, u32), world: &World) {
+    let this_run = world.read_change_tick();
+    let changed = |ticks: Option<ComponentTicks>| {
+        ticks.is_some_and(|ticks| ticks.is_changed(before_render, this_run))
+    };
+
+    let storages = world.storages();
+    let changed_id = storages
+        .resources
+        .iter()
+        .map(|(component_id, resource)| (component_id, resource.get_ticks()))
+        .chain(
+            storages
+                .non_send_resources
+                .iter()
+                .map(|(component_id, resource)| (component_id, resource.get_ticks())),
+        )
+        .find_map(|(component_id, ticks)| changed(ticks).then_some(component_id));
+    #[cfg(feature = "check_render_component_writes")]
+    let changed_id = changed_id.or_else(|| {
+        world.iter_entities().find_map(|entity| {
+            entity
+                .archetype()
+                .components()
+                .find(|component_id| changed(entity.get_change_ticks_by_id(*component_id)))
+        })
+    });
+
+    if let Some(component_id) = changed_id {
+        panic!(
+            "`{}` was mutated while rendering dioxus components, writes must be deferred",
+            world.components().get_name(component_id).unwrap()
+        );
+    }
+    assert_eq!(
+        world.entities().len(),
+        entity_count,
+        "Entities were spawned or despawned while rendering dioxus components, writes must be deferred"
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hooks::{use_resource, use_world},
+        prelude::*,
+        testing::DioxusTestApp,
+        DioxusStylesheet, DioxusUiPaused, DioxusUiRoot, DioxusUiSurface, DioxusUiTargetCamera,
+        DioxusUiTemplatePoolSize, DioxusUiUpdateRate, Theme, UiContext, UiRoot,
+    };
+    use bevy::{
+        ecs::{
+            entity::Entity,
+            query::{With, Without},
+            system::Resource,
+            world::World,
+        },
+        hierarchy::{BuildWorldChildren, Parent},
+        input::keyboard::KeyCode,
+        math::{Vec2, Vec3},
+        render::{
+            camera::{Camera, RenderTarget},
+            color::Color,
+            view::Visibility,
+        },
+        transform::{components::Transform, TransformBundle},
+        ui::{BackgroundColor, Node, Style, UiRect, UiScale, Val},
+        utils::default,
+        window::{Window, WindowRef, WindowResolution},
+    };
+    use dioxus::{
+        core::{Template, TemplateAttribute, TemplateNode},
+        prelude::*,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    fn ui_root(app: &DioxusTestApp) -> &UiRoot {
+        &app.world().non_send_resource::<UiContext>().roots[&app.root_entity()]
+    }
+
+    fn parent(app: &DioxusTestApp, entity: bevy::ecs::entity::Entity) -> bevy::ecs::entity::Entity {
+        app.world().get::<Parent>(entity).unwrap().get()
+    }
+
+    fn assert_element_maps_in_sync(ui_root: &UiRoot, world: &World) {
+        assert_eq!(
+            ui_root.element_id_to_bevy_ui_entity.len(),
+            ui_root.bevy_ui_entity_to_element_id.len()
+        );
+        for (element_id, entity) in &ui_root.element_id_to_bevy_ui_entity {
+            assert_eq!(
+                ui_root.bevy_ui_entity_to_element_id.get(entity),
+                Some(element_id)
+            );
+            assert!(world.get_entity(*entity).is_some());
+        }
+    }
+
+    #[test]
+    fn mounts_templates_below_the_root() {
+        fn Mount(cx: Scope) -> Element {
+            render! {
+                node {
+                    data_testid: "outer",
+                    node { data_testid: "inner", "Hello" }
+                }
+            }
+        }
+
+        let app = DioxusTestApp::new(Mount);
+        let outer = app.find_by_test_id("outer").unwrap();
+        let inner = app.find_by_test_id("inner").unwrap();
+        let text = app.find_by_text("Hello").unwrap();
+
+        assert_eq!(parent(&app, outer), app.root_entity());
+        assert_eq!(parent(&app, inner), outer);
+        assert_eq!(parent(&app, text), inner);
+    }
+
+    #[test]
+    fn updates_dynamic_attributes() {
+        fn AttributeUpdate(cx: Scope) -> Element {
+            let active = use_state(cx, || false);
+            render! {
+                node {
+                    data_testid: "button",
+                    onclick: move |_| active.set(true),
+                    width: if **active { "20" } else { "10" },
+                    background_color: if **active { "#ff0000" } else { "#0000ff" },
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(AttributeUpdate);
+        let button = app.find_by_test_id("button").unwrap();
+        assert_eq!(
+            app.world().get::<Style>(button).unwrap().width,
+            Val::Px(10.0)
+        );
+        assert_eq!(
+            app.world().get::<BackgroundColor>(button).unwrap().0,
+            Color::BLUE
+        );
+
+        app.click(button);
+        assert_eq!(app.find_by_test_id("button"), Some(button));
+        assert_eq!(
+            app.world().get::<Style>(button).unwrap().width,
+            Val::Px(20.0)
+        );
+        assert_eq!(
+            app.world().get::<BackgroundColor>(button).unwrap().0,
+            Color::RED
+        );
+    }
+
+    #[test]
+    fn removes_nodes() {
+        fn Removal(cx: Scope) -> Element {
+            let shown = use_state(cx, || true);
+            render! {
+                node {
+                    data_testid: "toggle",
+                    onclick: move |_| shown.set(!shown.get()),
+                    "Toggle"
+                }
+                if **shown {
+                    rsx! { node { data_testid: "removable", "Removable" } }
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Removal);
+        let toggle = app.find_by_test_id("toggle").unwrap();
+        let removable = app.find_by_test_id("removable").unwrap();
+
+        app.click(toggle);
+        assert_eq!(app.find_by_test_id("removable"), None);
+        assert_eq!(app.find_by_text("Removable"), None);
+        assert!(!ui_root(&app)
+            .bevy_ui_entity_to_element_id
+            .contains_key(&removable));
+
+        app.click(toggle);
+        assert!(app.find_by_test_id("removable").is_some());
+        assert!(app.find_by_text("Removable").is_some());
+    }
+
+    #[test]
+    fn bubbles_events_from_text_to_ancestor_nodes() {
+        fn Bubbling(cx: Scope) -> Element {
+            let outer_clicks = use_state(cx, || 0);
+            let inner_clicks = use_state(cx, || 0);
+            render! {
+                node {
+                    onclick: move |_| outer_clicks.modify(|clicks| clicks + 1),
+                    node {
+                        onclick: move |_| inner_clicks.modify(|clicks| clicks + 1),
+                        node { "inner {inner_clicks} outer {outer_clicks}" }
+                    }
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Bubbling);
+        let text = app.find_by_text("inner 0 outer 0").unwrap();
+
+        app.click(text);
+        assert!(app.find_by_text("inner 1 outer 1").is_some());
+    }
+
+    #[test]
+    fn sends_keyboard_events_to_the_hovered_node() {
+        fn Keyboard(cx: Scope) -> Element {
+            let hovered = use_state(cx, || false);
+            let key_code = use_state(cx, || None::<KeyCode>);
+            let label = format!("{} {:?}", hovered.get(), key_code.get());
+            render! {
+                node {
+                    data_testid: "target",
+                    onmouseover: move |_| hovered.set(true),
+                    onkeydown: move |event| key_code.set(event.key_code),
+                    "{label}"
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Keyboard);
+        let target = app.find_by_test_id("target").unwrap();
+
+        app.press_key(KeyCode::Space);
+        assert!(app.find_by_text("false None").is_some());
+
+        app.hover(target);
+        assert!(app.find_by_text("true None").is_some());
+
+        app.press_key(KeyCode::Space);
+        assert!(app.find_by_text("true Some(Space)").is_some());
+    }
+
+    fn Rows(cx: Scope) -> Element {
+        let rows = use_state(cx, || 3);
+        render! {
+            node {
+                data_testid: "toggle",
+                onclick: move |_| rows.set(if **rows == 3 { 1 } else { 3 }),
+                "Rows {rows}"
+            }
+            for row in 0..**rows {
+                node {
+                    key: "{row}",
+                    padding: "{row}",
+                    node {
+                        onclick: move |_| {},
+                        "Row {row}"
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_element_maps_in_sync_when_removing_nested_nodes() {
+        let mut app = DioxusTestApp::new(Rows);
+        let toggle = app.find_by_test_id("toggle").unwrap();
+        assert_element_maps_in_sync(ui_root(&app), app.world());
+
+        for _ in 0..3 {
+            app.click(toggle);
+            assert!(app.find_by_text("Row 2").is_none());
+            assert_element_maps_in_sync(ui_root(&app), app.world());
+
+            app.click(toggle);
+            assert!(app.find_by_text("Row 2").is_some());
+            assert_element_maps_in_sync(ui_root(&app), app.world());
+        }
+    }
+
+    #[test]
+    fn despawning_roots_leaves_no_entities() {
+        let mut app = DioxusTestApp::new(Rows);
+        app.despawn_root();
+        let entity_count = app.world().entities().len();
+
+        for _ in 0..3 {
+            app.spawn_root(DioxusUiRoot::new(Rows));
+            assert!(app.find_by_text("Row 2").is_some());
+            assert_element_maps_in_sync(ui_root(&app), app.world());
+
+            // Parks the removed rows, which must be despawned along with the root as well
+            let toggle = app.find_by_test_id("toggle").unwrap();
+            app.click(toggle);
+            assert_element_maps_in_sync(ui_root(&app), app.world());
+
+            app.despawn_root();
+            assert!(app
+                .world()
+                .non_send_resource::<UiContext>()
+                .roots
+                .is_empty());
+            assert_eq!(app.world().entities().len(), entity_count);
+        }
+    }
+
+    #[test]
+    fn hot_reloaded_templates_replace_live_instances() {
+        fn Greeting(cx: Scope) -> Element {
+            render! {
+                node { data_testid: "greeting", "Hello" }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Greeting);
+        let greeting = app.find_by_test_id("greeting").unwrap();
+        let name = ui_root(&app).templates.keys().next().unwrap().clone();
+
+        app.replace_template(Template {
+            name: Box::leak(name.into_boxed_str()),
+            roots: &[TemplateNode::Element {
+                tag: "node",
+                namespace: None,
+                attrs: &[TemplateAttribute::Static {
+                    name: "data-testid",
+                    value: "greeting",
+                    namespace: None,
+                }],
+                children: &[TemplateNode::Text {
+                    text: "Hello, hot reload",
+                }],
+            }],
+            node_paths: &[],
+            attr_paths: &[],
+        });
+
+        assert!(app.find_by_text("Hello, hot reload").is_some());
+        assert_eq!(app.find_by_text("Hello"), None);
+        assert_ne!(app.find_by_test_id("greeting"), Some(greeting));
+        assert!(app.world().get_entity(greeting).is_none());
+        assert_element_maps_in_sync(ui_root(&app), app.world());
+    }
+
+    #[test]
+    fn resolves_viewport_units_against_the_target_window() {
+        fn Viewport(cx: Scope) -> Element {
+            render! {
+                node { data_testid: "viewport", width: "50vw", height: "10vh" }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Viewport);
+        let node = app.find_by_test_id("viewport").unwrap();
+        let size = |app: &DioxusTestApp| {
+            let style = app.world().get::<Style>(node).unwrap();
+            (style.width, style.height)
+        };
+        assert_eq!(size(&app), (Val::Px(640.0), Val::Px(72.0)));
+
+        let window = app
+            .world_mut()
+            .spawn(Window {
+                resolution: WindowResolution::new(400.0, 300.0),
+                ..Default::default()
+            })
+            .id();
+        let camera = app
+            .world_mut()
+            .spawn(Camera {
+                target: RenderTarget::Window(WindowRef::Entity(window)),
+                ..Default::default()
+            })
+            .id();
+        let root_entity = app.root_entity();
+        app.world_mut()
+            .entity_mut(root_entity)
+            .insert(DioxusUiTargetCamera(camera));
+        app.update();
+        assert_eq!(size(&app), (Val::Px(200.0), Val::Px(30.0)));
+
+        app.world_mut()
+            .get_mut::<Window>(window)
+            .unwrap()
+            .resolution
+            .set(800.0, 600.0);
+        app.update();
+        assert_eq!(size(&app), (Val::Px(400.0), Val::Px(60.0)));
+
+        app.world_mut().insert_resource(UiScale(2.0));
+        app.update();
+        assert_eq!(size(&app), (Val::Px(200.0), Val::Px(30.0)));
+    }
+
+    #[test]
+    fn skips_invalid_attribute_values() {
+        fn Invalid(cx: Scope) -> Element {
+            render! {
+                node { data_testid: "invalid", width: "10", background_color: "#nope" }
+            }
+        }
+
+        let app = DioxusTestApp::new(Invalid);
+        let node = app.find_by_test_id("invalid").unwrap();
+        assert_eq!(app.world().get::<Style>(node).unwrap().width, Val::Px(10.0));
+        assert_eq!(
+            app.world().get::<BackgroundColor>(node).unwrap().0,
+            Color::NONE
+        );
+    }
+
+    #[test]
+    fn falls_back_to_default_tokens_missing_from_the_theme() {
+        fn Themed(cx: Scope) -> Element {
+            render! {
+                node { data_testid: "themed", width: "var(--spacing-4)", background_color: "var(--accent)" }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Themed);
+        let node = app.find_by_test_id("themed").unwrap();
+        app.world_mut()
+            .insert_resource(Theme::empty().with_token("accent", "red"));
+        app.update();
+        assert_eq!(app.world().get::<Style>(node).unwrap().width, Val::Px(16.0));
+        assert_eq!(
+            app.world().get::<BackgroundColor>(node).unwrap().0,
+            Color::RED
+        );
+    }
+
+    fn card_stylesheet() -> DioxusStylesheet {
+        DioxusStylesheet::default()
+            .with_class("card", [("padding", "8"), ("background-color", "red")])
+            .with_class("wide", [("padding", "16"), ("width", "100")])
+    }
+
+    /// Mounts `root_component` below an app that already has the card stylesheet.
+    fn styled_app(root_component: fn(Scope) -> Element) -> DioxusTestApp {
+        fn Empty(cx: Scope) -> Element {
+            render! { node {} }
+        }
+
+        let mut app = DioxusTestApp::new(Empty);
+        app.world_mut().insert_resource(card_stylesheet());
+        app.spawn_root(DioxusUiRoot::new(root_component));
+        app
+    }
+
+    #[test]
+    fn merges_classes_in_order() {
+        fn Classes(cx: Scope) -> Element {
+            render! {
+                node { data_testid: "card", class: "card wide" }
+                node { data_testid: "reversed", class: "wide card" }
+            }
+        }
+
+        let app = styled_app(Classes);
+        let card = app.find_by_test_id("card").unwrap();
+        let card_style = app.world().get::<Style>(card).unwrap();
+        assert_eq!(card_style.padding, UiRect::all(Val::Px(16.0)));
+        assert_eq!(card_style.width, Val::Px(100.0));
+        assert_eq!(
+            app.world().get::<BackgroundColor>(card).unwrap().0,
+            Color::RED
+        );
+
+        let reversed = app.find_by_test_id("reversed").unwrap();
+        let reversed_style = app.world().get::<Style>(reversed).unwrap();
+        assert_eq!(reversed_style.padding, UiRect::all(Val::Px(8.0)));
+        assert_eq!(reversed_style.width, Val::Px(100.0));
+    }
+
+    #[test]
+    fn inline_attributes_override_classes() {
+        fn Inline(cx: Scope) -> Element {
+            render! {
+                node { data_testid: "card", class: "card", padding: "2" }
+            }
+        }
+
+        let app = styled_app(Inline);
+        let card = app.find_by_test_id("card").unwrap();
+        assert_eq!(
+            app.world().get::<Style>(card).unwrap().padding,
+            UiRect::all(Val::Px(2.0))
+        );
+        assert_eq!(
+            app.world().get::<BackgroundColor>(card).unwrap().0,
+            Color::RED
+        );
+    }
+
+    #[test]
+    fn restyles_mounted_nodes_when_the_stylesheet_changes() {
+        static RENDERS: AtomicUsize = AtomicUsize::new(0);
+
+        fn Card(cx: Scope) -> Element {
+            RENDERS.fetch_add(1, Ordering::SeqCst);
+            render! {
+                node { data_testid: "card", class: "card" }
+            }
+        }
+
+        let mut app = styled_app(Card);
+        let card = app.find_by_test_id("card").unwrap();
+        let renders = RENDERS.load(Ordering::SeqCst);
+
+        app.world_mut()
+            .resource_mut::<DioxusStylesheet>()
+            .insert_class("card", [("padding", "4"), ("background-color", "blue")]);
+        app.update();
+        assert_eq!(
+            app.world().get::<Style>(card).unwrap().padding,
+            UiRect::all(Val::Px(4.0))
+        );
+        assert_eq!(
+            app.world().get::<BackgroundColor>(card).unwrap().0,
+            Color::BLUE
+        );
+        // Restyling doesn't rerender components
+        assert_eq!(RENDERS.load(Ordering::SeqCst), renders);
+    }
+
+    #[derive(Resource)]
+    struct Volume(u32);
+
+    #[test]
+    fn listeners_read_the_current_world() {
+        fn Settings(cx: Scope) -> Element {
+            let volume = use_resource::<Volume>(cx);
+            let heard = use_state(cx, || 0);
+            render! {
+                node { data_testid: "settings", onclick: move |_| heard.set(volume.0), "Heard {heard}" }
+            }
+        }
+
+        fn Empty(cx: Scope) -> Element {
+            render! { node {} }
+        }
+
+        // Volume must exist before Settings first renders
+        let mut app = DioxusTestApp::new(Empty);
+        app.world_mut().insert_resource(Volume(3));
+        app.spawn_root(DioxusUiRoot::new(Settings));
+
+        let settings = app.find_by_test_id("settings").unwrap();
+        app.click(settings);
+        assert!(app.find_by_text("Heard 3").is_some());
+
+        // The listener reads the resource when it's called, not when it was rendered
+        app.world_mut().insert_resource(Volume(7));
+        app.click(settings);
+        assert!(app.find_by_text("Heard 7").is_some());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "was mutated while rendering dioxus components")]
+    fn panics_when_a_render_mutates_a_resource() {
+        fn Mutating(cx: Scope) -> Element {
+            let world = use_world(cx);
+            // SAFETY: Nothing else accesses Volume while rendering. The write deliberately breaks
+            // the read-only contract of renders, to check that it's caught.
+            let volume = unsafe {
+                world
+                    .as_unsafe_world_cell_readonly()
+                    .get_resource_mut::<Volume>()
+            };
+            if let Some(mut volume) = volume {
+                volume.0 += 1;
+            }
+            render! { "Mutating" }
+        }
+
+        let mut app = DioxusTestApp::new(Mutating);
+        app.world_mut().insert_resource(Volume(0));
+        app.update();
+    }
+
+    #[test]
+    fn zero_update_rates_dont_limit_renders() {
+        fn Clicks(cx: Scope) -> Element {
+            let clicks = use_state(cx, || 0);
+            render! {
+                node { data_testid: "clicks", onclick: move |_| clicks.modify(|clicks| clicks + 1), "Clicks: {clicks}" }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Clicks);
+        let root_entity = app.root_entity();
+        app.world_mut()
+            .entity_mut(root_entity)
+            .insert(DioxusUiUpdateRate(0.0));
+
+        let clicks = app.find_by_test_id("clicks").unwrap();
+        for expected in 1..=3 {
+            app.click(clicks);
+            assert!(app.find_by_text(&format!("Clicks: {expected}")).is_some());
+        }
+    }
+
+    #[test]
+    fn paused_and_hidden_roots_render_changes_once_resumed() {
+        static RENDERS: AtomicUsize = AtomicUsize::new(0);
+
+        fn Score(cx: Scope) -> Element {
+            RENDERS.fetch_add(1, Ordering::SeqCst);
+            let volume = use_resource::<Volume>(cx).0;
+            render! { node { "Volume: {volume}" } }
+        }
+
+        fn Empty(cx: Scope) -> Element {
+            render! { node {} }
+        }
+
+        let mut app = DioxusTestApp::new(Empty);
+        app.world_mut().insert_resource(Volume(0));
+        let root_entity = app.spawn_root(DioxusUiRoot::new(Score));
+        let renders = || RENDERS.load(Ordering::SeqCst);
+        let renders_before_pause = renders();
+
+        app.world_mut()
+            .entity_mut(root_entity)
+            .insert(DioxusUiPaused);
+        for volume in 1..=3 {
+            app.world_mut().insert_resource(Volume(volume));
+            app.update();
+        }
+        assert!(app.find_by_text("Volume: 0").is_some());
+        assert_eq!(renders(), renders_before_pause);
+
+        // All changes while paused are rendered at once
+        app.world_mut()
+            .entity_mut(root_entity)
+            .remove::<DioxusUiPaused>();
+        app.update();
+        assert!(app.find_by_text("Volume: 3").is_some());
+        assert_eq!(renders(), renders_before_pause + 1);
+
+        let hidden_parent = app.world_mut().spawn(Visibility::Hidden).id();
+        app.world_mut()
+            .entity_mut(hidden_parent)
+            .add_child(root_entity);
+        app.world_mut().insert_resource(Volume(4));
+        app.update();
+        assert!(app.find_by_text("Volume: 3").is_some());
+
+        *app.world_mut()
+            .get_mut::<Visibility>(hidden_parent)
+            .unwrap() = Visibility::Visible;
+        app.update();
+        assert!(app.find_by_text("Volume: 4").is_some());
+    }
+
+    #[test]
+    fn maps_surface_hits_to_the_nodes_under_them() {
+        static CLICKS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        fn Terminal(cx: Scope) -> Element {
+            let record = |side: &str, position: Vec2| {
+                CLICKS
+                    .lock()
+                    .unwrap()
+                    .push(format!("{side} at {} {}", position.x, position.y));
+            };
+            render! {
+                node {
+                    node {
+                        data_testid: "left",
+                        width: "100px",
+                        height: "100px",
+                        onclick: move |event| record("Left", event.position),
+                    }
+                    node {
+                        data_testid: "right",
+                        width: "100px",
+                        height: "100px",
+                        onclick: move |event| record("Right", event.position),
+                    }
+                }
+            }
+        }
+
+        let mut app = DioxusTestApp::new(Terminal);
+        let root_entity = app.root_entity();
+        *app.world_mut().get_mut::<Style>(root_entity).unwrap() = Style {
+            width: Val::Px(200.0),
+            height: Val::Px(100.0),
+            ..default()
+        };
+        // A 2x1 quad, so each half of the quad shows one of the 100x100 nodes
+        let mesh = app
+            .world_mut()
+            .spawn((
+                TransformBundle::from_transform(Transform::from_xyz(10.0, 2.0, -5.0)),
+                DioxusUiSurface {
+                    root: root_entity,
+                    size: Vec2::new(2.0, 1.0),
+                },
+            ))
+            .id();
+        app.update();
+
+        let root_size = app.world().get::<Node>(root_entity).unwrap().size();
+        assert_eq!(root_size, Vec2::new(200.0, 100.0));
+        let right = app.find_by_test_id("right").unwrap();
+        assert_eq!(parent(&app, parent(&app, right)), root_entity);
+
+        app.click_at(mesh, Vec3::new(10.5, 2.0, -5.0));
+        app.click_at(mesh, Vec3::new(9.5, 2.25, -5.0));
+        // Hits outside of the quad don't reach the UI
+        app.click_at(mesh, Vec3::new(11.5, 2.0, -5.0));
+        assert_eq!(
+            *CLICKS.lock().unwrap(),
+            ["Right at 150 50", "Left at 50 25"]
+        );
+    }
+
+    #[test]
+    fn despawns_removed_instances_once_the_pool_is_full() {
+        #[derive(Resource)]
+        struct Rows(usize);
+
+        fn List(cx: Scope) -> Element {
+            let rows = use_resource::<Rows>(cx).0;
+            render! {
+                node {
+                    for row in 0..rows {
+                        node { key: "{row}", "Row {row}" }
+                    }
+                }
+            }
+        }
+
+        fn Empty(cx: Scope) -> Element {
+            render! { node {} }
+        }
+
+        let mut app = DioxusTestApp::new(Empty);
+        app.world_mut().insert_resource(Rows(3));
+        app.world_mut().insert_resource(DioxusUiTemplatePoolSize(1));
+        app.spawn_root(DioxusUiRoot::new(List));
+        assert!(app.find_by_text("Row 2").is_some());
+
+        app.world_mut().insert_resource(Rows(0));
+        app.update();
+
+        // Parked instances are the only unparented nodes besides the Empty and List roots
+        let world = app.world_mut();
+        let unparented = world
+            .query_filtered::<Entity, (With<Node>, Without<Parent>)>()
+            .iter(world)
+            .count();
+        assert_eq!(unparented, 2 + 1);
+        assert!(app.find_by_text("Row 0").is_none());
+    }
+}